@@ -1,13 +1,19 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
 use crossterm::event::{Event, KeyCode, KeyModifiers, MouseEventKind};
-use regex::Regex;
 use tui::backend::Backend;
 use tui::layout::Rect;
 
+use super::config::Config;
 use super::filter_list::{FilterList, FilterListReaction};
+use super::input::Input;
 use super::status_line::{StatusLine, StatusLineContent, StatusLineReaction};
 use super::text_area::TextArea;
 use crate::ty::{React, Render, RenderCursor, RenderWithState};
-use sherlog::{Sherlog, TextLineRef};
+use sherlog::{
+    CaseMode, ChildProcess, ContextLines, Filter, Level, LineFeed, PatternKind, Query, Sherlog,
+    TextLine, TimeSpec,
+};
 
 pub(crate) struct App {
     core: Sherlog,
@@ -15,34 +21,102 @@ pub(crate) struct App {
     text: TextArea,
     status: StatusLine,
     filters: FilterList,
+    /// Line sources delivering new lines. Feed index is the origin of lines it delivers.
+    feeds: Vec<Box<dyn LineFeed>>,
+    process: Option<ChildProcess>,
 
     focus: Focus,
     pub wants_quit: bool,
     search_issued: bool,
+    /// Filter set with query command, applied together with the filter list
+    query: Option<Filter>,
+    /// Lines less severe are hidden, set with level command independently of the filter list
+    min_level: Option<Level>,
+    /// Lines shown around lines passing filters, set with context command
+    context: ContextLines,
+    /// Multi-line records are folded to their first line unless toggled
+    fold_all: bool,
+    /// Records folded differently than `fold_all` says, by their first line
+    fold_toggled: BTreeSet<usize>,
+    config: Config,
 }
 
 impl App {
-    pub fn new(core: Sherlog, filename: String, terminal_size: Rect) -> Self {
-        let line_count = core.line_count();
+    pub fn new(input: Input, config: Config, terminal_size: Rect) -> Self {
+        let line_count = input.core.line_count();
+        let format = input.core.parser_name().map(String::from);
         let mut app = App {
-            core,
+            core: input.core,
             terminal_size,
-            text: TextArea::new(),
+            text: TextArea::new(input.origins),
             status: StatusLine {
                 content: StatusLineContent::Status(String::from("Type `:` to start command")),
-                filename,
+                filename: input.name,
+                format,
                 line_count,
                 line_shown: None,
+                // In follow mode view starts pinned to the bottom
+                following: (!input.feeds.is_empty() && input.follow).then_some(true),
+                compression: input.compression,
+                process: None,
+                pattern_kind: PatternKind::default(),
+                case_mode: CaseMode::default(),
             },
             filters: FilterList::new(),
+            feeds: input.feeds,
+            process: input.process,
             focus: Focus::General,
             wants_quit: false,
             search_issued: false,
+            query: None,
+            min_level: None,
+            context: ContextLines::default(),
+            fold_all: false,
+            fold_toggled: BTreeSet::new(),
+            config,
         };
-        app.update_displayed_lines();
+        if app.status.following.is_some() {
+            app.go_bottom();
+        } else {
+            app.update_displayed_lines();
+        }
         app
     }
 
+    pub fn on_tick(&mut self) {
+        let mut appended = false;
+        for (origin, feed) in self.feeds.iter_mut().enumerate() {
+            match feed.poll() {
+                Ok(lines) if lines.is_empty() => {}
+                Ok(lines) => {
+                    self.core.append_from(origin as u16, lines);
+                    appended = true;
+                }
+                Err(e) => self.status.print_error(format!("Cannot read log: {e}")),
+            }
+        }
+        if appended {
+            self.status.line_count = self.core.line_count();
+            // Format of streamed log is detected as lines arrive
+            self.status.format = self.core.parser_name().map(String::from);
+            if self.status.following == Some(true) {
+                self.go_bottom();
+            } else {
+                // Fills the screen if it wasn't full yet
+                self.update_displayed_lines();
+            }
+        }
+
+        if let Some(process) = self.process.as_mut() {
+            match process.state() {
+                Ok(state) => self.status.process = Some(state),
+                Err(e) => self
+                    .status
+                    .print_error(format!("Cannot check process: {e}")),
+            }
+        }
+    }
+
     fn layout(area: Rect) -> Vec<Rect> {
         // Create two chunks for text view and command bar
         tui::layout::Layout::default()
@@ -74,6 +148,57 @@ impl App {
                 self.status.enter_search_mode(value);
                 self.focus = Focus::StatusLine;
             }
+            ["g" | "goto", ref rest @ ..] if !rest.is_empty() => self.go_to_time(&rest.join(" ")),
+            ["query"] => {
+                self.query = None;
+                self.apply_filters();
+                self.status.print_info("Query cleared");
+                self.update_displayed_lines();
+            }
+            ["query", ..] => self.query(command.trim_start()["query".len()..].trim_start()),
+            ["format", "auto"] => {
+                let detected = self.core.detect_parser(self.config.detection_candidates());
+                match detected {
+                    Some(format) => self.status.print_info(format!("Detected format: {format}")),
+                    None => self.status.print_info("Format not detected"),
+                }
+                self.on_format_changed();
+            }
+            ["format", "none"] => {
+                self.core.set_parser(None);
+                self.status.print_info("Lines are not parsed");
+                self.on_format_changed();
+            }
+            ["format", format] => match self.config.parser(format) {
+                Ok(parser) => {
+                    self.core.set_parser(Some(parser));
+                    self.status.print_info(format!("Format: {format}"));
+                    self.on_format_changed();
+                }
+                Err(e) => err = Some(e.to_string()),
+            },
+            ["level"] | ["level", "off"] => self.set_min_level(None),
+            ["level", level] => match level.parse() {
+                Ok(level) => self.set_min_level(Some(level)),
+                Err(e) => err = Some(e),
+            },
+            ["context"] | ["context", "off"] => self.set_context(ContextLines::default()),
+            ["context", ref lines @ ..] if lines.len() <= 2 => {
+                match lines
+                    .iter()
+                    .map(|n| n.parse())
+                    .collect::<Result<Vec<usize>, _>>()
+                {
+                    // More context than the log has would only cost time
+                    Ok(lines) => self.set_context(ContextLines {
+                        before: lines[0].min(self.core.line_count()),
+                        after: lines[lines.len() - 1].min(self.core.line_count()),
+                    }),
+                    Err(e) => err = Some(format!("Invalid number of context lines: {e}")),
+                }
+            }
+            ["fold"] => self.fold_all(true),
+            ["unfold"] => self.fold_all(false),
             ["w" | "wrap"] => {
                 if self.text.toggle_wrap() {
                     self.status.print_info("word wrap on");
@@ -88,31 +213,186 @@ impl App {
         }
     }
 
+    fn set_min_level(&mut self, level: Option<Level>) {
+        self.min_level = level;
+        self.apply_filters();
+        match level {
+            Some(level) => self.status.print_info(format!("Showing {level} and above")),
+            None => self.status.print_info("Showing all levels"),
+        }
+        self.update_displayed_lines();
+    }
+
+    /// Sets lines shown before and after lines passing filters
+    fn set_context(&mut self, context: ContextLines) {
+        self.context = context;
+        self.apply_filters();
+        if context == ContextLines::default() {
+            self.status.print_info("Context lines off");
+        } else {
+            self.status.print_info(format!(
+                "Context lines: {} before, {} after",
+                context.before, context.after
+            ));
+        }
+        self.update_displayed_lines();
+    }
+
+    fn fold_all(&mut self, fold: bool) {
+        self.fold_all = fold;
+        self.fold_toggled.clear();
+        if fold {
+            self.status.print_info("All records folded");
+        } else {
+            self.status.print_info("All records unfolded");
+        }
+        // Top line may have been folded into its record
+        let top = self.core.record(self.first_displayed_line_num()).start;
+        self.display_lines(top, DisplayDirection::Forward);
+    }
+
+    /// Folds or unfolds the first multi-line record in view
+    fn toggle_fold(&mut self) {
+        let record = self
+            .text
+            .lines
+            .iter()
+            .map(|line| self.core.record(line.line_num))
+            .find(|record| record.len() > 1);
+        let Some(record) = record else {
+            return self.status.print_info("No multi-line record in view");
+        };
+        self.toggle_record_fold(record.start);
+        let top = self.first_displayed_line_num().min(record.start);
+        self.display_lines(top, DisplayDirection::Forward);
+    }
+
+    fn toggle_record_fold(&mut self, record_start: usize) {
+        if !self.fold_toggled.remove(&record_start) {
+            self.fold_toggled.insert(record_start);
+        }
+    }
+
+    fn is_folded(&self, record_start: usize) -> bool {
+        self.fold_all != self.fold_toggled.contains(&record_start)
+    }
+
+    /// Unfolds the record hiding line `n` and moves view to the line
+    fn go_to_line(&mut self, n: usize) {
+        let record = self.core.record(n);
+        if record.start != n && self.is_folded(record.start) {
+            self.toggle_record_fold(record.start);
+        }
+        self.display_lines(n, DisplayDirection::Forward);
+    }
+
+    /// Up to `cnt` lines from line `n` on (or up to line `n` in reverse), skipping lines hidden in folded records.
+    /// Also returns number of lines hidden under every folded record head.
+    fn visible_lines(
+        &self,
+        n: usize,
+        cnt: usize,
+        dir: DisplayDirection,
+    ) -> (Vec<TextLine>, HashMap<usize, usize>) {
+        let folding = self.fold_all || !self.fold_toggled.is_empty();
+        let mut lines = Vec::new();
+        let mut folded = HashMap::new();
+        let mut next = Some(n);
+        while let Some(n) = next.filter(|_| lines.len() < cnt) {
+            let line = match dir {
+                DisplayDirection::Forward => self.core.get_lines(n, Some(1)),
+                DisplayDirection::Reverse => self.core.get_lines_rev(n, Some(1)),
+            }
+            .pop();
+            let Some(line) = line else {
+                break;
+            };
+            let n = line.line_num;
+            let record = if folding {
+                self.core.record(n)
+            } else {
+                n..n + 1
+            };
+            let is_folded = record.len() > 1 && self.is_folded(record.start);
+            let is_hidden = is_folded && n != record.start;
+            if !is_hidden {
+                if is_folded {
+                    folded.insert(n, record.len() - 1);
+                }
+                lines.push(line.to_text_line());
+            }
+            next = match dir {
+                DisplayDirection::Forward if is_folded => Some(record.end),
+                DisplayDirection::Forward => Some(n + 1),
+                DisplayDirection::Reverse if is_hidden => Some(record.start),
+                DisplayDirection::Reverse => n.checked_sub(1),
+            };
+        }
+        if let DisplayDirection::Reverse = dir {
+            lines.reverse();
+        }
+        (lines, folded)
+    }
+
+    /// Field filters depend on the format, so filtered lines may have changed
+    fn on_format_changed(&mut self) {
+        self.status.format = self.core.parser_name().map(String::from);
+        self.update_displayed_lines();
+    }
+
     fn display_lines(&mut self, n: usize, dir: DisplayDirection) {
         // Until the text widget is aware of it's position within provided lines
         // we need to request exact number of lines for correct navigation.
         let request_cnt = (self.terminal_size.height - 1) as usize;
 
-        let new_lines: Vec<_> = match dir {
-            DisplayDirection::Forward if request_cnt + n > self.core.line_count() => self
-                .core
-                .get_lines_rev(self.core.line_count() - 1, Some(request_cnt)),
-
-            DisplayDirection::Forward => self.core.get_lines(n, Some(request_cnt)),
-            DisplayDirection::Reverse if n < request_cnt => {
-                self.core.get_lines(0, Some(request_cnt))
+        let (from, dir) = match dir {
+            DisplayDirection::Forward if request_cnt + n > self.core.line_count() => (
+                self.core.line_count().saturating_sub(1),
+                DisplayDirection::Reverse,
+            ),
+            DisplayDirection::Reverse if n < request_cnt => (0, DisplayDirection::Forward),
+            dir => (n, dir),
+        };
+        let (mut new_lines, folded) = self.visible_lines(from, request_cnt, dir);
+
+        let mut separated = HashSet::new();
+        if self.context != ContextLines::default() {
+            separated = hunk_starts(&new_lines, &folded);
+            // Separators take rows of the lines farthest from where display starts
+            while new_lines.len() + separated.len() > request_cnt {
+                match dir {
+                    DisplayDirection::Forward => {
+                        if let Some(last) = new_lines.pop() {
+                            separated.remove(&last.line_num);
+                        }
+                    }
+                    DisplayDirection::Reverse => {
+                        new_lines.remove(0);
+                        if let Some(first) = new_lines.first() {
+                            separated.remove(&first.line_num);
+                        }
+                    }
+                }
             }
-            DisplayDirection::Reverse => self.core.get_lines_rev(n, Some(request_cnt)),
         }
-        .iter()
-        .map(TextLineRef::to_text_line)
-        .collect();
 
         // Don't update view if we reached end
         if !new_lines.is_empty() {
             let new_line_idx = new_lines.first().map(|first| first.line_num);
             self.status.line_shown = new_line_idx;
             self.text.lines = new_lines;
+            self.text.folded = folded;
+            self.text.separated = separated;
+        }
+
+        if self.status.following.is_some() {
+            // Last line may be hidden in a folded record
+            let last_shown = self
+                .text
+                .last_line()
+                .map(|l| l.line_num + self.text.folded.get(&l.line_num).unwrap_or(&0));
+            let at_bottom = last_shown == self.core.last_line_num();
+            self.status.following = Some(at_bottom);
         }
     }
 
@@ -120,18 +400,29 @@ impl App {
         self.display_lines(self.first_displayed_line_num(), DisplayDirection::Forward);
     }
 
+    /// Scrolls by `n` visible lines, folded records count as one
     fn scroll_up(&mut self, n: usize) {
-        self.display_lines(
-            self.last_displayed_line_num().saturating_sub(n),
+        let (lines, _) = self.visible_lines(
+            self.last_displayed_line_num(),
+            n.saturating_add(1),
             DisplayDirection::Reverse,
         );
+        let last = lines.first().map_or(0, |l| l.line_num);
+        self.display_lines(last, DisplayDirection::Reverse);
     }
 
+    /// Scrolls by `n` visible lines, folded records count as one
     fn scroll_down(&mut self, n: usize) {
-        self.display_lines(
-            self.first_displayed_line_num().saturating_add(n),
+        let (lines, _) = self.visible_lines(
+            self.first_displayed_line_num(),
+            n.saturating_add(1),
             DisplayDirection::Forward,
         );
+        match lines.get(n) {
+            Some(line) => self.display_lines(line.line_num, DisplayDirection::Forward),
+            // Shows the last lines
+            None => self.display_lines(self.core.line_count(), DisplayDirection::Forward),
+        }
     }
 
     fn go_top(&mut self) {
@@ -147,9 +438,9 @@ impl App {
             self.core.highlight(None);
             self.status.clear();
         } else {
-            match Regex::new(pattern) {
-                Ok(re) => {
-                    self.core.highlight(Some(re));
+            match self.status.make_pattern(pattern) {
+                Ok(pattern) => {
+                    self.core.highlight(Some(pattern.into()));
                     self.status.clear();
                 }
                 Err(e) => self.status.print_error(format!("Invalid pattern: {e}")),
@@ -164,9 +455,9 @@ impl App {
             self.core.search(None);
             self.status.print_info("Search cleared");
         } else {
-            match Regex::new(pattern) {
-                Ok(re) => {
-                    self.core.search(Some(re));
+            match self.status.make_pattern(pattern) {
+                Ok(pattern) => {
+                    self.core.search(Some(pattern.into()));
                     self.status.clear();
                 }
                 Err(e) => self
@@ -180,7 +471,7 @@ impl App {
             {
                 Some(n) => {
                     self.search_issued = true;
-                    self.display_lines(n, DisplayDirection::Forward);
+                    self.go_to_line(n);
                 }
                 None => self
                     .status
@@ -189,6 +480,56 @@ impl App {
         }
     }
 
+    /// Applies filter list together with the query and level threshold. Returns number of filters from the list.
+    fn apply_filters(&mut self) -> usize {
+        let current = self.core.record_timestamp(self.first_displayed_line_num());
+        let mut filters = self
+            .filters
+            .make_filters(current, self.core.last_timestamp());
+        let list_len = filters.len();
+        filters.extend(self.query.clone());
+        filters.extend(self.min_level.map(Filter::MinLevel));
+        self.core.filter_with_context(filters, self.context);
+        list_len
+    }
+
+    fn query(&mut self, query: &str) {
+        let current = self.core.record_timestamp(self.first_displayed_line_num());
+        match Query::parse(query, current) {
+            Ok(query) => {
+                self.query = Some(query.filter);
+                self.apply_filters();
+                self.core.highlight(query.highlight);
+                self.status.print_info("Query applied");
+                self.update_displayed_lines();
+            }
+            Err(e) => self.status.print_error(format!("Invalid query: {e}")),
+        }
+    }
+
+    /// Moves view to the first line at or after given time. Relative time and time of day refer to the top line.
+    fn go_to_time(&mut self, spec: &str) {
+        let spec: TimeSpec = match spec.parse() {
+            Ok(spec) => spec,
+            Err(e) => return self.status.print_error(e),
+        };
+        let reference = self.core.record_timestamp(self.first_displayed_line_num());
+        let Some(time) = spec.resolve(reference) else {
+            return self
+                .status
+                .print_error("Current line has no timestamp to count from");
+        };
+        match self.core.find_time(time) {
+            Some(n) => {
+                self.status.clear();
+                self.go_to_line(n);
+            }
+            None => self
+                .status
+                .print_info(format!("No lines at or after {}", time.naive_utc())),
+        }
+    }
+
     fn first_displayed_line_num(&self) -> usize {
         self.text.first_line().map(|l| l.line_num).unwrap_or(0)
     }
@@ -215,7 +556,7 @@ impl App {
                 .next_search_result(self.first_displayed_line_num() + 1)
             {
                 Some(n) => {
-                    self.display_lines(n, DisplayDirection::Forward);
+                    self.go_to_line(n);
                 }
                 None => {
                     self.status.print_info("No more results below");
@@ -234,7 +575,7 @@ impl App {
                 .prev_search_result(self.first_displayed_line_num() - 1)
             {
                 Some(n) => {
-                    self.display_lines(n, DisplayDirection::Forward);
+                    self.go_to_line(n);
                 }
                 None => {
                     self.status.print_info("No more results upwards");
@@ -243,6 +584,31 @@ impl App {
         }
     }
 
+    /// Moves view to the next line of at least `min` level
+    fn go_to_next_level(&mut self, min: Level) {
+        match self
+            .core
+            .next_level_line(self.first_displayed_line_num() + 1, min)
+        {
+            Some(n) => self.go_to_line(n),
+            None => self.status.print_info(format!("No more {min} lines below")),
+        }
+    }
+
+    /// Moves view to the previous line of at least `min` level
+    fn go_to_prev_level(&mut self, min: Level) {
+        let found = self
+            .first_displayed_line_num()
+            .checked_sub(1)
+            .and_then(|start| self.core.prev_level_line(start, min));
+        match found {
+            Some(n) => self.go_to_line(n),
+            None => self
+                .status
+                .print_info(format!("No more {min} lines upwards")),
+        }
+    }
+
     pub fn render<B: Backend>(&mut self, f: &mut tui::Frame<B>) {
         let area = f.size();
         let layout = App::layout(area);
@@ -286,6 +652,7 @@ impl App {
 const TEXT_LAYOUT_IDX: usize = 0;
 const STATUS_LAYOUT_IDX: usize = 1;
 
+#[derive(Clone, Copy)]
 enum DisplayDirection {
     Forward,
     Reverse,
@@ -318,10 +685,7 @@ impl<'a> React<'a> for App {
                 FilterListReaction::Nothing => {}
                 FilterListReaction::Defocus => {
                     self.focus = Focus::General;
-                    let applied_filters = self.filters.make_regex_filter_vec();
-                    let applied_filters_len = applied_filters.len();
-                    self.core.filter(self.filters.make_regex_filter_vec());
-                    match applied_filters_len {
+                    match self.apply_filters() {
                         0 => self
                             .status
                             .print_info("no filters applied - log unfiltered"),
@@ -354,10 +718,15 @@ impl<'a> React<'a> for App {
                     KeyCode::Char('f') => {
                         self.focus = Focus::Filters;
                         self.status
-                            .print_info("<a>add  <e>edit  <d>disable (toggle) <n>negate (toggle)");
+                            .print_info("<a>add <e>edit <d>disable <n>negate <t>kind <r>regex <c>case <g>group <o>any/all <>>/<<>nest");
                     }
                     KeyCode::Char('n') => self.go_to_next_search_result(),
                     KeyCode::Char('N') => self.go_to_prev_search_result(),
+                    KeyCode::Char('e') => self.go_to_next_level(Level::Error),
+                    KeyCode::Char('E') => self.go_to_prev_level(Level::Error),
+                    KeyCode::Char('w') => self.go_to_next_level(Level::Warn),
+                    KeyCode::Char('W') => self.go_to_prev_level(Level::Warn),
+                    KeyCode::Char('z') => self.toggle_fold(),
                     _ => {}
                 };
             }
@@ -379,6 +748,18 @@ impl<'a> React<'a> for App {
     }
 }
 
+/// Displayed lines not following the preceding one (or the end of its folded record) directly
+fn hunk_starts(lines: &[TextLine], folded: &HashMap<usize, usize>) -> HashSet<usize> {
+    lines
+        .windows(2)
+        .filter(|pair| {
+            let hidden = folded.get(&pair[0].line_num).unwrap_or(&0);
+            pair[0].line_num + hidden + 1 != pair[1].line_num
+        })
+        .map(|pair| pair[1].line_num)
+        .collect()
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Focus {
     General,
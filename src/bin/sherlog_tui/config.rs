@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use sherlog::{LineParser, LogFormat, RegexParser};
+
+/// User settings read from a JSON file:
+///
+/// ```json
+/// {
+///     "parsers": [
+///         {
+///             "name": "myapp",
+///             "pattern": "(?P<ts>\\S+) \\[(?P<level>\\w+)\\] (?P<msg>.*)",
+///             "files": ["myapp*.log", "/var/log/myapp/*"]
+///         }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub(crate) struct Config {
+    pub parsers: Vec<ParserConfig>,
+}
+
+/// Custom line parser made of a named-capture regex. Its name selects it with `--format`.
+#[derive(Debug)]
+pub(crate) struct ParserConfig {
+    pub parser: RegexParser,
+    /// Globs of files the parser is used for when no format is given. Globs with `/` match the whole path, others
+    /// just the file name.
+    pub files: Vec<String>,
+}
+
+impl Config {
+    /// Loads config from `path`, or from `$XDG_CONFIG_HOME/sherlog/config.json` (`~/.config/...`) if not given.
+    /// Missing default config is not an error.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                Config::parse(&text).with_context(|| format!("Invalid config {}", path.display()))
+            }
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+                Ok(Config::default())
+            }
+            Err(e) => Err(e).with_context(|| format!("Cannot read config {}", path.display())),
+        }
+    }
+
+    fn parse(text: &str) -> Result<Config> {
+        let config: Value = serde_json::from_str(text)?;
+        let parsers = match config.get("parsers") {
+            Some(Value::Array(parsers)) => parsers
+                .iter()
+                .map(ParserConfig::parse)
+                .collect::<Result<_>>()?,
+            Some(_) => bail!("parsers must be an array"),
+            None => Vec::new(),
+        };
+        Ok(Config { parsers })
+    }
+
+    /// Parser for `--format`: custom parser of that name or a built-in format
+    pub fn parser(&self, format: &str) -> Result<Box<dyn LineParser>> {
+        match self.parsers.iter().find(|p| p.parser.name() == format) {
+            Some(custom) => Ok(Box::new(custom.parser.clone())),
+            None => format
+                .parse::<LogFormat>()
+                .map(|f| f.parser())
+                .map_err(|e| anyhow::anyhow!("{e} or a parser from config")),
+        }
+    }
+
+    /// First custom parser whose globs match one of the files
+    pub fn parser_for_files(&self, paths: &[String]) -> Option<Box<dyn LineParser>> {
+        paths.iter().find_map(|path| {
+            let custom = self.parsers.iter().find(|p| p.matches_file(path))?;
+            Some(Box::new(custom.parser.clone()) as Box<dyn LineParser>)
+        })
+    }
+
+    /// Parsers taking part in format detection: custom parsers first, then built-in formats
+    pub fn detection_candidates(&self) -> Vec<Box<dyn LineParser>> {
+        let custom = self
+            .parsers
+            .iter()
+            .map(|p| Box::new(p.parser.clone()) as Box<dyn LineParser>);
+        let builtin = LogFormat::DETECTABLE.iter().map(LogFormat::parser);
+        custom.chain(builtin).collect()
+    }
+}
+
+impl ParserConfig {
+    fn parse(value: &Value) -> Result<ParserConfig> {
+        let string = |key: &str| match value.get(key) {
+            Some(Value::String(s)) => Ok(s.clone()),
+            _ => Err(anyhow::anyhow!("parser {key} must be a string")),
+        };
+        let name = string("name")?;
+        let parser = RegexParser::new(&name, &string("pattern")?)
+            .map_err(|e| anyhow::anyhow!("parser {name}: {e}"))?;
+        let files = match value.get("files") {
+            Some(Value::Array(files)) => files
+                .iter()
+                .map(|f| f.as_str().map(String::from))
+                .collect::<Option<_>>()
+                .with_context(|| format!("parser {name}: files must be strings"))?,
+            Some(_) => bail!("parser {name}: files must be an array"),
+            None => Vec::new(),
+        };
+        Ok(ParserConfig { parser, files })
+    }
+
+    fn matches_file(&self, path: &str) -> bool {
+        let name = Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        self.files.iter().any(|glob| {
+            let target = if glob.contains('/') { path } else { &name };
+            glob_match(glob, target)
+        })
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_dir.join("sherlog").join("config.json"))
+}
+
+/// Matches text against glob with `*` (any sequence) and `?` (any character) wildcards
+fn glob_match(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut g, mut t) = (0, 0);
+    // Position of the last `*` and text position it currently matches up to, to backtrack on mismatch
+    let mut star = None;
+    while t < text.len() {
+        match glob.get(g) {
+            Some('*') => {
+                star = Some((g, t));
+                g += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                g += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_g, star_t)) => {
+                    g = star_g + 1;
+                    t = star_t + 1;
+                    star = Some((star_g, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|c| *c == '*')
+}
@@ -2,13 +2,14 @@ use std::fmt::Display;
 use std::str::FromStr;
 
 use crossterm::event::KeyCode;
-use regex::Regex;
 use tui::style::{Color, Style};
 use tui::widgets::{Block, BorderType, Borders, List, ListItem, ListState};
 
-use crate::ty::{Cursor, React, RenderWithState};
+use crate::ty::{pattern_marker, Cursor, React, RenderWithState};
 use crate::widgets::{ListWithCursor, OpaqueOverlay};
-use sherlog::RegexFilter;
+use sherlog::{
+    CaseMode, FieldFilter, Filter, Pattern, PatternKind, RegexFilter, TimeWindow, Timestamp,
+};
 
 #[derive(Default)]
 pub struct FilterList {
@@ -49,19 +50,8 @@ impl FilterList {
         }
     }
 
-    fn build_list_item(f: &FilterEntry) -> ListItem {
-        let mut s = String::new();
-        if !f.active {
-            s.push('#')
-        }
-        if f.negate {
-            s.push('!');
-        }
-        if !s.is_empty() {
-            s.push(' ')
-        }
-        s.push_str(f.value.as_str());
-        ListItem::new(s)
+    fn build_list_item(f: &FilterEntry) -> ListItem<'_> {
+        ListItem::new(format!("{}{}", f.prefix(), f.value.as_str()))
     }
 
     fn on_edit_insert_char(&mut self, c: char) {
@@ -102,22 +92,90 @@ impl FilterList {
         }
     }
 
-    fn append_new(&mut self) {
+    fn append_new(&mut self, mut entry: FilterEntry) {
+        // Entry added below a group becomes its first member
+        entry.depth = match self.selected_entry() {
+            Some(selected) if selected.value.is_group() => selected.depth + 1,
+            Some(selected) => selected.depth,
+            None => 0,
+        };
         self.entries.insert(
             (self.selected().unwrap_or_default() + 1).clamp(0, self.entries.len()),
-            FilterEntry::new(""),
+            entry,
         );
     }
 
     fn insert_new(&mut self) {
+        let mut entry = FilterEntry::new("");
+        entry.depth = self.selected_entry().map(|e| e.depth).unwrap_or_default();
         self.entries.insert(
             self.selected()
                 .unwrap_or_default()
                 .clamp(0, self.entries.len()),
-            FilterEntry::new(""),
+            entry,
         );
     }
 
+    /// Range of entries made of the selected entry and all its nested entries
+    fn selected_subtree(&self) -> Option<std::ops::Range<usize>> {
+        let selected = self.selected().filter(|s| *s < self.entries.len())?;
+        let depth = self.entries[selected].depth;
+        let len = self.entries[selected + 1..]
+            .iter()
+            .take_while(|e| e.depth > depth)
+            .count();
+        Some(selected..selected + 1 + len)
+    }
+
+    /// Moves selected entry with its nested entries into the group above
+    fn indent_selected(&mut self) {
+        if let Some(subtree) = self.selected_subtree() {
+            let Some(prev) = subtree.start.checked_sub(1).map(|p| &self.entries[p]) else {
+                return;
+            };
+            let max_depth = prev.depth + usize::from(prev.value.is_group());
+            if self.entries[subtree.start].depth < max_depth {
+                self.entries[subtree].iter_mut().for_each(|e| e.depth += 1);
+            }
+        }
+    }
+
+    /// Moves selected entry with its nested entries out of its group
+    fn outdent_selected(&mut self) {
+        if let Some(subtree) = self.selected_subtree() {
+            if self.entries[subtree.start].depth > 0 {
+                self.entries[subtree].iter_mut().for_each(|e| e.depth -= 1);
+            }
+        }
+    }
+
+    fn toggle_pattern_kind_selected(&mut self) {
+        if let Some(filter) = self.selected_filter_mut() {
+            filter.set_pattern_options(filter.pattern_kind.toggled(), filter.case_mode);
+        }
+    }
+
+    fn cycle_entry_kind_selected(&mut self) {
+        if let Some(filter) = self.selected_filter_mut() {
+            filter.set_kind(filter.kind.cycled());
+        }
+    }
+
+    fn cycle_case_mode_selected(&mut self) {
+        if let Some(filter) = self.selected_filter_mut() {
+            filter.set_pattern_options(filter.pattern_kind, filter.case_mode.cycled());
+        }
+    }
+
+    fn toggle_group_kind_selected(&mut self) {
+        if let Some(FilterValue::Group(kind)) = self.selected_filter_mut().map(|f| &mut f.value) {
+            *kind = match kind {
+                GroupKind::All => GroupKind::Any,
+                GroupKind::Any => GroupKind::All,
+            }
+        }
+    }
+
     fn selected_filter_mut(&mut self) -> Option<&mut FilterEntry> {
         self.selected().and_then(|s| self.entries.get_mut(s))
     }
@@ -134,27 +192,58 @@ impl FilterList {
         }
     }
 
+    /// Deletes selected entry. Members of deleted group are moved one level up.
     fn delete_selected(&mut self) {
-        if let Some(selected) = self.selected() {
-            if selected < self.entries.len() {
-                self.entries.remove(selected);
-            }
+        if let Some(subtree) = self.selected_subtree() {
+            self.entries[subtree.clone()]
+                .iter_mut()
+                .skip(1)
+                .for_each(|e| e.depth -= 1);
+            self.entries.remove(subtree.start);
         }
     }
 
     fn edit_selected(&mut self) {
-        self.edit_cursor = self.selected_entry().map(|e| e.value.as_str().len() as u16);
+        self.edit_cursor = self
+            .selected_entry()
+            .filter(|e| !e.value.is_group())
+            .map(|e| e.value.as_str().len() as u16);
     }
 
     fn selected_entry(&self) -> Option<&FilterEntry> {
         self.selected().and_then(|s| self.entries.get(s))
     }
 
-    pub fn make_regex_filter_vec(&self) -> Vec<RegexFilter> {
-        self.entries
-            .iter()
-            .filter_map(FilterEntry::try_to_regex_filter)
-            .collect()
+    /// Builds filters of active entries. Time windows are resolved against the `current` line timestamp and the
+    /// `end` of the log, entries that cannot be resolved are skipped.
+    ///
+    /// Top level entries are combined as if they were members of an "all of" group.
+    pub fn make_filters(&self, current: Option<Timestamp>, end: Option<Timestamp>) -> Vec<Filter> {
+        self.make_group_members(&mut 0, 0, current, end)
+    }
+
+    /// Builds filters of consecutive entries nested at least `depth` levels deep, starting at `pos`. Entries nested
+    /// deeper than their predecessor allows are treated as members of the current group.
+    fn make_group_members(
+        &self,
+        pos: &mut usize,
+        depth: usize,
+        current: Option<Timestamp>,
+        end: Option<Timestamp>,
+    ) -> Vec<Filter> {
+        let mut filters = Vec::new();
+        while let Some(entry) = self.entries.get(*pos).filter(|e| e.depth >= depth) {
+            *pos += 1;
+            let filter = match &entry.value {
+                FilterValue::Group(kind) => {
+                    let members = self.make_group_members(pos, entry.depth + 1, current, end);
+                    entry.try_to_group_filter(*kind, members)
+                }
+                _ => entry.try_to_filter(current, end),
+            };
+            filters.extend(filter);
+        }
+        filters
     }
 
     fn cursor(&self) -> Option<Cursor> {
@@ -162,17 +251,7 @@ impl FilterList {
             self.edit_cursor
                 .zip(self.entries.get(selected))
                 .map(|(cursor, filter)| {
-                    // This offset should be connected with build_list_item
-                    let mut offset = 0;
-                    if filter.negate {
-                        offset += 1;
-                    }
-                    if !filter.active {
-                        offset += 1;
-                    }
-                    if offset > 0 {
-                        offset += 1;
-                    }
+                    let offset = filter.prefix().chars().count() as u16;
                     Cursor::new(cursor + offset, selected as u16)
                 })
         })
@@ -227,10 +306,20 @@ impl<'a> React<'a> for FilterList {
             KeyCode::Up => self.select_prev(),
             KeyCode::Down => self.select_next(),
             KeyCode::Char('a') => {
-                self.append_new();
+                self.append_new(FilterEntry::new(""));
                 self.select_next();
                 self.edit_selected();
             }
+            KeyCode::Char('g') => {
+                self.append_new(FilterEntry::group(GroupKind::Any));
+                self.select_next();
+            }
+            KeyCode::Char('o') => self.toggle_group_kind_selected(),
+            KeyCode::Char('r') => self.toggle_pattern_kind_selected(),
+            KeyCode::Char('t') => self.cycle_entry_kind_selected(),
+            KeyCode::Char('c') => self.cycle_case_mode_selected(),
+            KeyCode::Char('>') | KeyCode::Tab => self.indent_selected(),
+            KeyCode::Char('<') | KeyCode::BackTab => self.outdent_selected(),
             KeyCode::Char('i') => {
                 self.insert_new();
                 if self.selected().is_none() {
@@ -259,19 +348,83 @@ pub enum FilterListReaction {
 
 pub struct FilterEntry {
     pub value: FilterValue,
+    pub kind: EntryKind,
     pub negate: bool,
     pub active: bool,
+    /// Nesting level. Entry belongs to the closest group above it having smaller depth.
+    pub depth: usize,
+    pub pattern_kind: PatternKind,
+    pub case_mode: CaseMode,
 }
 
 impl FilterEntry {
     pub fn new(s: &str) -> Self {
+        let (kind, pattern_kind, case_mode) = Default::default();
         FilterEntry {
-            value: FilterValue::new(s),
+            value: FilterValue::new(s, kind, pattern_kind, case_mode),
+            kind,
             negate: false,
             active: true,
+            depth: 0,
+            pattern_kind,
+            case_mode,
+        }
+    }
+
+    pub fn group(kind: GroupKind) -> Self {
+        FilterEntry {
+            value: FilterValue::Group(kind),
+            ..FilterEntry::new("")
         }
     }
 
+    /// Everything displayed before the value: indentation, flags and pattern options
+    fn prefix(&self) -> String {
+        let mut s = " ".repeat(self.depth * INDENT_WIDTH);
+        if !self.active {
+            s.push('#')
+        }
+        if self.negate {
+            s.push('!');
+        }
+        if s.len() > self.depth * INDENT_WIDTH {
+            s.push(' ')
+        }
+        if self.is_pattern() {
+            s.push_str(&pattern_marker(self.pattern_kind, self.case_mode));
+            s.push(' ');
+        }
+        if let Some(marker) = self.kind.marker().filter(|_| !self.value.is_group()) {
+            s.push(marker);
+            s.push(' ');
+        }
+        s
+    }
+
+    /// Tells if value is (possibly invalid) text pattern
+    fn is_pattern(&self) -> bool {
+        self.kind == EntryKind::Pattern && !self.value.is_group()
+    }
+
+    fn set_kind(&mut self, kind: EntryKind) {
+        if !self.value.is_group() {
+            self.kind = kind;
+            self.reparse(String::from(self.value.as_str()));
+        }
+    }
+
+    fn set_pattern_options(&mut self, kind: PatternKind, case: CaseMode) {
+        if self.is_pattern() {
+            self.pattern_kind = kind;
+            self.case_mode = case;
+            self.reparse(String::from(self.value.as_str()));
+        }
+    }
+
+    fn reparse(&mut self, s: String) {
+        self.value = FilterValue::new(&s, self.kind, self.pattern_kind, self.case_mode);
+    }
+
     pub fn backspace_at(&mut self, pos: usize) {
         let old = self.value.as_str();
         let (left, right) = old.split_at(pos);
@@ -280,26 +433,53 @@ impl FilterEntry {
         }
 
         let new_left = &left[0..left.len() - 1];
-        let new = format!("{new_left}{right}");
-        self.value = FilterValue::new(&new);
+        self.reparse(format!("{new_left}{right}"));
     }
 
     pub fn insert_at(&mut self, c: char, pos: usize) {
         let old = self.value.as_str();
 
         let (left, right) = old.split_at(pos);
-        let new = format!("{left}{c}{right}");
-        self.value = FilterValue::new(&new);
+        self.reparse(format!("{left}{c}{right}"));
     }
 
-    pub fn try_to_regex_filter(&self) -> Option<RegexFilter> {
+    pub fn try_to_filter(
+        &self,
+        current: Option<Timestamp>,
+        end: Option<Timestamp>,
+    ) -> Option<Filter> {
         match &self.value {
-            FilterValue::Valid(pattern) if self.active => Some(RegexFilter {
-                pattern: pattern.clone(),
+            _ if !self.active => None,
+            FilterValue::Valid(pattern) => Some(Filter::Regex(RegexFilter {
+                pattern: pattern.regex().clone(),
                 negate: self.negate,
+            })),
+            FilterValue::Time(window, _) => window.resolve(current, end).map(|mut filter| {
+                filter.negate = self.negate;
+                Filter::Time(filter)
             }),
-            _ => None,
+            FilterValue::Field(filter, _) => Some(Filter::Field(FieldFilter {
+                negate: self.negate,
+                ..filter.clone()
+            })),
+            FilterValue::Group(_) | FilterValue::Invalid(_) => None,
+        }
+    }
+
+    /// Combines filters of group members. Empty and inactive groups make no filter.
+    fn try_to_group_filter(&self, kind: GroupKind, members: Vec<Filter>) -> Option<Filter> {
+        if !self.active || members.is_empty() {
+            return None;
         }
+        let group = match kind {
+            GroupKind::All => Filter::All(members),
+            GroupKind::Any => Filter::Any(members),
+        };
+        Some(if self.negate {
+            Filter::Not(Box::new(group))
+        } else {
+            group
+        })
     }
 }
 
@@ -310,22 +490,89 @@ impl Display for FilterEntry {
 }
 
 pub enum FilterValue {
-    Valid(Regex),
+    Valid(Pattern),
+    /// Time window with the text it was parsed from, see [`EntryKind::Time`]
+    Time(TimeWindow, String),
+    /// Condition on a parsed field written as `field op value` (e.g. `status >= 500`), with its text, see
+    /// [`EntryKind::Field`]
+    Field(FieldFilter, String),
+    /// Groups entries nested below
+    Group(GroupKind),
     Invalid(String),
 }
 
-impl FilterValue {
-    fn new(s: &str) -> Self {
-        match Regex::from_str(s) {
-            Ok(r) => FilterValue::Valid(r),
-            Err(_) => FilterValue::Invalid(String::from(s)),
+/// What the text of filter entry is read as, switched with a key like pattern options
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EntryKind {
+    /// Text pattern, see [`PatternKind`]
+    #[default]
+    Pattern,
+    /// Time window, e.g. `10:00..10:30`, `last 5m` or `~1m`
+    Time,
+    /// Condition on a parsed field, e.g. `status >= 500`
+    Field,
+}
+
+impl EntryKind {
+    fn cycled(self) -> Self {
+        match self {
+            EntryKind::Pattern => EntryKind::Time,
+            EntryKind::Time => EntryKind::Field,
+            EntryKind::Field => EntryKind::Pattern,
+        }
+    }
+
+    /// Shown in front of entries of this kind
+    fn marker(self) -> Option<char> {
+        match self {
+            EntryKind::Pattern => None,
+            EntryKind::Time => Some(TIME_FILTER_MARKER),
+            EntryKind::Field => Some(FIELD_FILTER_MARKER),
         }
     }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupKind {
+    All,
+    Any,
+}
+
+/// Number of spaces nested entries are shifted by
+const INDENT_WIDTH: usize = 2;
+
+/// Marks time window entries in the list
+const TIME_FILTER_MARKER: char = '@';
+
+/// Marks field condition entries in the list
+const FIELD_FILTER_MARKER: char = '%';
+
+impl FilterValue {
+    fn new(s: &str, kind: EntryKind, pattern_kind: PatternKind, case: CaseMode) -> Self {
+        let value = match kind {
+            EntryKind::Pattern => Pattern::new(s, pattern_kind, case)
+                .map(FilterValue::Valid)
+                .ok(),
+            EntryKind::Time => TimeWindow::from_str(s)
+                .map(|w| FilterValue::Time(w, String::from(s)))
+                .ok(),
+            EntryKind::Field => FieldFilter::from_str(s)
+                .map(|filter| FilterValue::Field(filter, String::from(s)))
+                .ok(),
+        };
+        value.unwrap_or_else(|| FilterValue::Invalid(String::from(s)))
+    }
 
     pub fn as_str(&self) -> &str {
         match self {
-            FilterValue::Valid(r) => r.as_str(),
-            FilterValue::Invalid(s) => s,
+            FilterValue::Valid(p) => p.as_str(),
+            FilterValue::Time(_, s) | FilterValue::Field(_, s) | FilterValue::Invalid(s) => s,
+            FilterValue::Group(GroupKind::All) => "all of:",
+            FilterValue::Group(GroupKind::Any) => "any of:",
         }
     }
+
+    pub fn is_group(&self) -> bool {
+        matches!(self, FilterValue::Group(_))
+    }
 }
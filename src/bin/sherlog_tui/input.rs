@@ -0,0 +1,222 @@
+use std::io::IsTerminal;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use sherlog::{
+    source, ChildProcess, Compression, Encoding, FileFollower, JournalExportReader, LineFeed,
+    LineSource, MemorySource, Sherlog, StreamReader,
+};
+use tui::style::{Color, Modifier, Style};
+
+/// Log opened for viewing, together with everything frontend needs to know about its origin
+pub(crate) struct Input {
+    pub core: Sherlog,
+    pub name: String,
+    pub compression: Option<Compression>,
+    /// Deliver lines arriving after the input was opened. Feed index is used as the origin of its lines.
+    pub feeds: Vec<Box<dyn LineFeed>>,
+    /// Line origins, indexed with [`sherlog::TextLine::origin`]. Empty when all lines come from a single origin.
+    pub origins: Vec<Origin>,
+    pub process: Option<ChildProcess>,
+    /// View should stick to the bottom as new lines arrive
+    pub follow: bool,
+}
+
+/// How lines of given origin are marked in the text area gutter
+#[derive(Clone, Debug)]
+pub(crate) struct Origin {
+    pub tag: String,
+    pub style: Style,
+}
+
+impl Input {
+    fn new(core: Sherlog, name: String) -> Self {
+        Input {
+            core,
+            name,
+            compression: None,
+            feeds: Vec::new(),
+            origins: Vec::new(),
+            process: None,
+            follow: false,
+        }
+    }
+}
+
+pub(crate) fn open_file(path: &str, encoding: Option<Encoding>, follow: bool) -> Result<Input> {
+    let file = open_source(path, encoding, follow)?;
+    let mut input = Input::new(Sherlog::with_source(file.source), file_name(path));
+    input
+        .feeds
+        .extend(file.follower.map(|f| Box::new(f) as Box<dyn LineFeed>));
+    input.compression = file.compression;
+    input.follow = follow;
+    Ok(input)
+}
+
+/// Merges files into one timeline. Each file is a separate origin tagged with its name.
+pub(crate) fn open_files(
+    paths: &[String],
+    encoding: Option<Encoding>,
+    follow: bool,
+) -> Result<Input> {
+    let mut sources = Vec::new();
+    let mut feeds: Vec<Box<dyn LineFeed>> = Vec::new();
+    let mut origins = Vec::new();
+    for (i, path) in paths.iter().enumerate() {
+        let file = open_source(path, encoding, follow)?;
+        sources.push(file.source);
+        feeds.extend(file.follower.map(|f| Box::new(f) as Box<dyn LineFeed>));
+        let tag = Path::new(path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().chars().take(MAX_TAG_LEN).collect())
+            .unwrap_or_default();
+        origins.push(Origin {
+            tag,
+            style: Style::default().fg(ORIGIN_COLORS[i % ORIGIN_COLORS.len()]),
+        });
+    }
+
+    let name = format!("{} +{}", file_name(&paths[0]), paths.len() - 1);
+    let mut input = Input::new(Sherlog::merge(sources), name);
+    input.feeds = feeds;
+    input.origins = origins;
+    input.follow = follow;
+    Ok(input)
+}
+
+/// Longest file name prefix used as origin tag of merged files
+const MAX_TAG_LEN: usize = 8;
+
+/// Colors distinguishing merged files
+const ORIGIN_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Green,
+    Color::Magenta,
+    Color::Blue,
+    Color::Red,
+];
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or(String::from("invalid_filename"))
+}
+
+/// Most bytes of a followed file read when opening it. Older lines are not shown.
+const FOLLOW_TAIL_LEN: u64 = 64 * 1024 * 1024;
+
+/// Single file ready to be viewed
+struct OpenedFile {
+    source: Box<dyn LineSource>,
+    /// Delivers lines appended to the file, present only when following
+    follower: Option<FileFollower>,
+    compression: Option<Compression>,
+}
+
+fn open_source(path: &str, encoding: Option<Encoding>, follow: bool) -> Result<OpenedFile> {
+    let compression = Compression::detect_file(path)?;
+    match compression {
+        Some(compression) if follow => bail!("Cannot follow {compression} compressed file"),
+        None if follow && encoding.unwrap_or(Encoding::Utf8) != Encoding::Utf8 => {
+            bail!("Only utf-8 files can be followed")
+        }
+        None if follow => {
+            // Mapping would fault on reading lines of a file truncated meanwhile, so only the end of followed file
+            // is read into memory
+            let (source, follower) = FileFollower::open(path, FOLLOW_TAIL_LEN)
+                .with_context(|| format!("Cannot open {path}"))?;
+            Ok(OpenedFile {
+                source: Box::new(source),
+                follower: Some(follower),
+                compression: None,
+            })
+        }
+        _ => Ok(OpenedFile {
+            source: source::open_file(path, encoding)
+                .with_context(|| format!("Cannot open {path}"))?,
+            follower: None,
+            compression,
+        }),
+    }
+}
+
+/// Streams standard input. User input is then read from the controlling terminal.
+pub(crate) fn open_stdin(encoding: Option<Encoding>, follow: bool) -> Result<Input> {
+    check_stdin()?;
+    let mut input = Input::new(Sherlog::new(""), String::from("stdin"));
+    input
+        .feeds
+        .push(Box::new(StreamReader::spawn(std::io::stdin(), encoding)));
+    input.follow = follow;
+    Ok(input)
+}
+
+fn check_stdin() -> Result<()> {
+    if std::io::stdin().is_terminal() {
+        bail!("No input. Provide LOG_FILE or pipe the log into standard input");
+    }
+    // Crossterm switches to /dev/tty by itself when stdin is not a terminal. Checking it upfront gives a readable
+    // error instead of failing somewhere in terminal setup.
+    #[cfg(unix)]
+    std::fs::File::open("/dev/tty").context("Cannot open /dev/tty for user input")?;
+    Ok(())
+}
+
+/// Opens `journalctl -o export` output from a file (possibly compressed) or standard input when `path` is `None`.
+/// Entries are converted to syslog lines.
+pub(crate) fn open_journal(path: Option<&str>, follow: bool) -> Result<Input> {
+    let Some(path) = path else {
+        check_stdin()?;
+        let mut input = Input::new(Sherlog::new(""), String::from("stdin"));
+        let reader = JournalExportReader::new(std::io::stdin());
+        input
+            .feeds
+            .push(Box::new(StreamReader::spawn(reader, None)));
+        input.follow = follow;
+        return Ok(input);
+    };
+
+    if follow {
+        bail!("Cannot follow journal export file");
+    }
+    let file = std::fs::File::open(path).with_context(|| format!("Cannot open {path}"))?;
+    let compression = Compression::detect_file(path)?;
+    let reader = match compression {
+        Some(compression) => compression.decoder(file)?,
+        None => Box::new(file),
+    };
+    let source = MemorySource::from_reader(JournalExportReader::new(reader), None)?;
+    let mut input = Input::new(Sherlog::with_source(Box::new(source)), file_name(path));
+    input.compression = compression;
+    Ok(input)
+}
+
+/// Runs a command and views its output. Stdout and stderr are kept as separate origins.
+pub(crate) fn run_command(
+    command: &[String],
+    encoding: Option<Encoding>,
+    follow: bool,
+) -> Result<Input> {
+    let (program, args) = command.split_first().context("Empty command")?;
+    let (process, stdout, stderr) = ChildProcess::spawn(program, args, encoding)
+        .with_context(|| format!("Cannot run {program}"))?;
+
+    let mut input = Input::new(Sherlog::new(""), program.clone());
+    input.feeds = vec![Box::new(stdout), Box::new(stderr)];
+    input.origins = vec![
+        Origin {
+            tag: String::from(" "),
+            style: Style::default(),
+        },
+        Origin {
+            tag: String::from("E"),
+            style: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        },
+    ];
+    input.process = Some(process);
+    input.follow = follow;
+    Ok(input)
+}
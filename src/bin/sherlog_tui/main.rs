@@ -1,28 +1,38 @@
 mod app;
+mod config;
 mod filter_list;
+mod input;
 mod status_line;
 mod text_area;
 mod ty;
 mod widgets;
 
-use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use anyhow::Result;
 use app::App;
 use clap::Parser;
+use config::Config;
 use crossterm::event;
 use crossterm::{execute, terminal};
 use log::{info, LevelFilter};
 use log4rs::append::file::FileAppender;
 use log4rs::config::{Appender, Root};
-use sherlog::Sherlog;
+use sherlog::{Encoding, LogFormat, RecordStart};
 use tui::backend::{Backend, CrosstermBackend};
 use tui::Terminal;
 
+/// How often app is woken up when there is no user input (e.g. to check for new lines in followed file)
+const TICK_INTERVAL: Duration = Duration::from_millis(250);
+
 fn run_app<B: Backend + std::io::Write>(terminal: &mut Terminal<B>, mut app: App) -> Result<()> {
     loop {
         terminal.draw(|f| app.render(f))?;
-        app.handle_event(event::read()?);
+        if event::poll(TICK_INTERVAL)? {
+            app.handle_event(event::read()?);
+        }
+        app.on_tick();
         if app.wants_quit {
             return Ok(());
         }
@@ -34,13 +44,42 @@ fn run_app<B: Backend + std::io::Write>(terminal: &mut Terminal<B>, mut app: App
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Input file
+    /// Input files. Multiple files are merged into one timeline by line timestamps. Standard input is read when
+    /// omitted or `-`
     #[arg(value_name = "LOG_FILE")]
-    input: String,
+    inputs: Vec<String>,
+
+    /// Command to run. Its output (stdout and stderr) is viewed as the log.
+    #[arg(last = true, value_name = "COMMAND", conflicts_with = "inputs")]
+    command: Vec<String>,
 
     /// Provide debug log during execution
     #[arg(short, long)]
     debug: bool,
+
+    /// Keep watching the file for new lines (like `tail -f`). Only the last 64 MiB of the file are loaded.
+    #[arg(short, long)]
+    follow: bool,
+
+    /// Input encoding: utf-8, latin1, utf-16le or utf-16be. Detected from byte order mark if not given, utf-8
+    /// otherwise. Undecodable bytes are always replaced.
+    #[arg(short, long)]
+    encoding: Option<Encoding>,
+
+    /// Log format used to extract line fields: json, logfmt, syslog, text, journal (`journalctl -o export` output)
+    /// or name of a parser from config. Picked by file name from config parsers or detected from the first lines if
+    /// not given.
+    #[arg(long)]
+    format: Option<String>,
+
+    /// Pattern matching the first line of a log record, or `line` to treat every line as a record. By default lines
+    /// without timestamp (e.g. stack traces) continue the record of the preceding line.
+    #[arg(long, value_name = "REGEX")]
+    record_start: Option<RecordStart>,
+
+    /// Config file. Defaults to `$XDG_CONFIG_HOME/sherlog/config.json`
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
 }
 
 fn restore_terminal() -> Result<()> {
@@ -71,14 +110,45 @@ fn main() -> Result<()> {
             )?;
 
         log4rs::init_config(config)?;
-        info!("Sherlog started with {}", args.input);
+        info!("Sherlog started with {:?}", args.inputs);
     }
 
-    let log_data = std::fs::read_to_string(&args.input)?;
-    let filename = Path::new(&args.input)
-        .file_name()
-        .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or(String::from("invalid_filename"));
+    let config = Config::load(args.config.as_deref())?;
+
+    let journal = args.format.as_deref() == Some(LogFormat::Journal.as_str());
+    let mut input = match args.inputs.as_slice() {
+        _ if journal && !args.command.is_empty() => {
+            anyhow::bail!("Use journal format with a file or standard input")
+        }
+        [] if journal => input::open_journal(None, args.follow)?,
+        [path] if journal && path == "-" => input::open_journal(None, args.follow)?,
+        [path] if journal => input::open_journal(Some(path), args.follow)?,
+        _ if journal => anyhow::bail!("Journal export files cannot be merged"),
+        _ if !args.command.is_empty() => {
+            input::run_command(&args.command, args.encoding, args.follow)?
+        }
+        [] => input::open_stdin(args.encoding, args.follow)?,
+        [path] if path == "-" => input::open_stdin(args.encoding, args.follow)?,
+        [path] => input::open_file(path, args.encoding, args.follow)?,
+        paths if paths.iter().any(|p| p == "-") => {
+            anyhow::bail!("Standard input cannot be merged with other files")
+        }
+        paths => input::open_files(paths, args.encoding, args.follow)?,
+    };
+    match &args.format {
+        Some(format) => input.core.set_parser(Some(config.parser(format)?)),
+        None => match config.parser_for_files(&args.inputs) {
+            Some(parser) => input.core.set_parser(Some(parser)),
+            // Built-in formats are detected already
+            None if !config.parsers.is_empty() => {
+                input.core.detect_parser(config.detection_candidates());
+            }
+            None => {}
+        },
+    }
+    if let Some(record_start) = args.record_start {
+        input.core.set_record_start(record_start);
+    }
 
     // setup terminal
     terminal::enable_raw_mode()?;
@@ -99,7 +169,7 @@ fn main() -> Result<()> {
     }));
 
     // main application loop
-    let app = App::new(Sherlog::new(&log_data), filename, terminal.size()?);
+    let app = App::new(input, config, terminal.size()?);
     let res = run_app(&mut terminal, app);
 
     restore_terminal()?;
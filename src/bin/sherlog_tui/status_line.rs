@@ -1,15 +1,26 @@
 use std::fmt::Display;
 
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, KeyModifiers};
+use sherlog::{CaseMode, Compression, Pattern, PatternKind, ProcessState};
 
-use crate::ty::{React, Render};
+use crate::ty::{pattern_marker, React, Render};
 use crate::widgets;
 
 pub(crate) struct StatusLine {
     pub content: StatusLineContent,
     pub filename: String,
+    /// Format lines are parsed as
+    pub format: Option<String>,
     pub line_count: usize,
     pub line_shown: Option<usize>,
+    /// Present in follow mode. Tells whether view is pinned to the bottom.
+    pub following: Option<bool>,
+    pub compression: Option<Compression>,
+    /// State of the process whose output is viewed
+    pub process: Option<ProcessState>,
+    /// Options of entered search and highlight patterns. Toggled while typing the pattern.
+    pub pattern_kind: PatternKind,
+    pub case_mode: CaseMode,
 }
 
 impl StatusLine {
@@ -36,6 +47,21 @@ impl StatusLine {
     pub fn enter_search_mode(&mut self, value: String) {
         self.content = StatusLineContent::SearchPattern(SearchKind::Search, value);
     }
+
+    /// Makes pattern with currently selected options
+    pub fn make_pattern(&self, text: &str) -> Result<Pattern, regex::Error> {
+        Pattern::new(text, self.pattern_kind, self.case_mode)
+    }
+
+    fn header(&self) -> Option<String> {
+        match &self.content {
+            StatusLineContent::SearchPattern(kind, _) => Some(format!(
+                "{kind} {}",
+                pattern_marker(self.pattern_kind, self.case_mode)
+            )),
+            content => content.header().map(String::from),
+        }
+    }
 }
 
 impl Render for StatusLine {
@@ -43,21 +69,37 @@ impl Render for StatusLine {
 
     fn widget(&self) -> Self::Widget<'_> {
         widgets::StatusLine::new()
-            .left_maybe(self.content.header())
+            .left_maybe(self.header())
             .left_maybe(self.content.editable())
             .cursor_maybe(self.content.editable().is_some())
             .right_maybe(
                 self.line_shown
                     .map(|line| format!("{}/{}", line, self.line_count)),
             )
+            .right_maybe(self.format.as_ref().map(|f| format!("<{f}>")))
+            .right_maybe(self.compression.map(|c| format!("[{c}]")))
             .right(self.filename.as_ref())
+            .right_maybe(self.process.map(|p| format!("[{p}]")))
+            .right_maybe(self.following.map(|pinned| match pinned {
+                true => "FOLLOW",
+                false => "FOLLOW (paused)",
+            }))
     }
 }
 
 impl<'a> React<'a> for StatusLine {
     type Reaction = Option<StatusLineReaction>;
     fn on_key(&'a mut self, key: crossterm::event::KeyEvent) -> Self::Reaction {
+        let editing_pattern = matches!(self.content, StatusLineContent::SearchPattern(..));
         match key.code {
+            KeyCode::Char('r') if editing_pattern && key.modifiers == KeyModifiers::CONTROL => {
+                self.pattern_kind = self.pattern_kind.toggled();
+                None
+            }
+            KeyCode::Char('t') if editing_pattern && key.modifiers == KeyModifiers::CONTROL => {
+                self.case_mode = self.case_mode.cycled();
+                None
+            }
             KeyCode::Char(input) => {
                 match &mut self.content {
                     StatusLineContent::Command(s) | StatusLineContent::SearchPattern(_, s) => {
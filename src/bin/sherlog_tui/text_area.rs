@@ -1,22 +1,35 @@
-use sherlog::{SpanKind, SpanRef, TextLine};
-use tui::style::{Color, Style};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use sherlog::{Level, SpanKind, SpanRef, TextLine};
+use tui::style::{Color, Modifier, Style};
 use tui::text::Spans;
 use tui::widgets::{Paragraph, Wrap};
 
+use crate::input::Origin;
 use crate::ty::Render;
 
 pub(crate) struct TextArea {
     pub x: usize,
     pub wrap: bool,
     pub lines: Vec<TextLine>,
+    /// Number of lines hidden in folded records, by line number of the displayed record head
+    pub folded: HashMap<usize, usize>,
+    /// Displayed lines not adjacent to the preceding one, rendered after a `--` separator
+    pub separated: HashSet<usize>,
+    /// Origin tags shown in the gutter. Gutter column is skipped when empty.
+    origins: Vec<Origin>,
 }
 
 impl TextArea {
-    pub fn new() -> Self {
+    pub fn new(origins: Vec<Origin>) -> Self {
         TextArea {
             x: 0,
             wrap: false,
             lines: vec![],
+            folded: HashMap::new(),
+            separated: HashSet::new(),
+            origins,
         }
     }
 
@@ -29,12 +42,17 @@ impl TextArea {
     }
 
     fn make_spans(line: &TextLine, offset: usize) -> tui::text::Spans<'_> {
+        let mut style = level_style(line.level);
+        if line.context {
+            style = style.add_modifier(Modifier::DIM);
+        }
         let mut chars_to_remove = offset;
         let spans = line.spans.iter();
         spans
             .filter_map(|s| {
-                if chars_to_remove >= s.content.len() {
-                    chars_to_remove -= s.content.len();
+                let char_count = s.content.chars().count();
+                if chars_to_remove >= char_count {
+                    chars_to_remove -= char_count;
                     None
                 } else {
                     let remaining = s.remove_left(chars_to_remove);
@@ -42,20 +60,64 @@ impl TextArea {
                     Some(remaining)
                 }
             })
-            .map(Self::make_span)
+            .map(|span| Self::make_span(span, style))
             .collect::<Vec<_>>()
             .into()
     }
 
-    fn make_span(span: SpanRef<'_>) -> tui::text::Span {
+    /// Styles span of a line rendered with `style`. Highlights on colored lines are reversed to stay visible.
+    fn make_span(span: SpanRef<'_>, style: Style) -> tui::text::Span<'_> {
+        let content = sanitize(span.content);
         match span.kind {
-            SpanKind::Raw => tui::text::Span::raw(span.content),
+            SpanKind::Raw => tui::text::Span::styled(content, style),
+            SpanKind::Highlight if style == Style::default() => {
+                tui::text::Span::styled(content, Style::default().fg(Color::Red))
+            }
             SpanKind::Highlight => {
-                tui::text::Span::styled(span.content, Style::default().fg(Color::Red))
+                tui::text::Span::styled(content, style.add_modifier(Modifier::REVERSED))
             }
         }
     }
 
+    /// Marks lines that need user attention. Rendered only if any of displayed lines has a mark.
+    fn make_lossy_mark(line: &TextLine) -> tui::text::Span<'static> {
+        if line.lossy {
+            tui::text::Span::styled("!", Style::default().fg(Color::Black).bg(Color::Magenta))
+        } else {
+            tui::text::Span::raw(" ")
+        }
+    }
+
+    fn make_origin_tag(&self, line: &TextLine, width: usize) -> tui::text::Span<'_> {
+        match self.origins.get(line.origin as usize) {
+            Some(origin) => tui::text::Span::styled(format!("{:width$}", origin.tag), origin.style),
+            None => tui::text::Span::raw(" ".repeat(width)),
+        }
+    }
+
+    fn make_gutter(&self, line: &TextLine, lossy_marks: bool) -> Vec<tui::text::Span<'_>> {
+        let mut gutter = Vec::new();
+        let tag_width = self.origins.iter().map(|o| o.tag.chars().count()).max();
+        if let Some(width) = tag_width {
+            gutter.push(self.make_origin_tag(line, width));
+        }
+        if lossy_marks {
+            gutter.push(Self::make_lossy_mark(line));
+        }
+        if !gutter.is_empty() {
+            gutter.push(tui::text::Span::raw(" "));
+        }
+        gutter
+    }
+
+    fn make_fold_marker(&self, line: &TextLine) -> Option<tui::text::Span<'static>> {
+        let hidden = self.folded.get(&line.line_num)?;
+        Some(tui::text::Span::styled(
+            format!(" [+{hidden} lines]"),
+            Style::default().fg(Color::DarkGray),
+        ))
+    }
+
     pub fn toggle_wrap(&mut self) -> bool {
         self.wrap = !self.wrap;
         self.wrap
@@ -74,11 +136,19 @@ impl Render for TextArea {
     type Widget<'a> = Paragraph<'a>;
 
     fn widget(&self) -> Paragraph<'_> {
-        let spans: Vec<Spans> = self
-            .lines
-            .iter()
-            .map(|line| Self::make_spans(line, self.x))
-            .collect();
+        let lossy_marks = self.lines.iter().any(|line| line.lossy);
+        let mut spans: Vec<Spans> = Vec::new();
+        for line in self.lines.iter() {
+            if self.separated.contains(&line.line_num) {
+                spans.push(
+                    tui::text::Span::styled("--", Style::default().fg(Color::DarkGray)).into(),
+                );
+            }
+            let mut line_spans = self.make_gutter(line, lossy_marks);
+            line_spans.extend(Self::make_spans(line, self.x).0);
+            line_spans.extend(self.make_fold_marker(line));
+            spans.push(line_spans.into());
+        }
         let mut paragraph = Paragraph::new(spans);
         if self.wrap {
             paragraph = paragraph.wrap(Wrap { trim: false })
@@ -86,3 +156,35 @@ impl Render for TextArea {
         paragraph
     }
 }
+
+/// Color of line text by its level. Info and unclassified lines keep the terminal colors.
+fn level_style(level: Option<Level>) -> Style {
+    match level {
+        Some(Level::Trace) => Style::default().fg(Color::DarkGray),
+        Some(Level::Debug) => Style::default().fg(Color::Blue),
+        Some(Level::Info) | None => Style::default(),
+        Some(Level::Warn) => Style::default().fg(Color::Yellow),
+        Some(Level::Error) => Style::default().fg(Color::Red),
+        Some(Level::Fatal) => Style::default()
+            .fg(Color::White)
+            .bg(Color::Red)
+            .add_modifier(Modifier::BOLD),
+    }
+}
+
+/// Control characters (e.g. from binary junk) would be interpreted by the terminal and break the layout.
+/// Tabs are expanded, everything else is replaced with the replacement character.
+fn sanitize(content: &str) -> Cow<'_, str> {
+    if !content.contains(|c: char| c.is_control()) {
+        return Cow::Borrowed(content);
+    }
+    let mut sanitized = String::with_capacity(content.len());
+    for c in content.chars() {
+        match c {
+            '\t' => sanitized.push_str("    "),
+            c if c.is_control() => sanitized.push(char::REPLACEMENT_CHARACTER),
+            c => sanitized.push(c),
+        }
+    }
+    Cow::Owned(sanitized)
+}
@@ -1,4 +1,5 @@
 use crossterm::event::{KeyEvent, MouseEvent};
+use sherlog::{CaseMode, PatternKind};
 
 // Unfortunately it's impossible to compose stateful widgets in this
 // design since this would require to also compose state references into a single object.
@@ -53,3 +54,18 @@ impl Cursor {
         }
     }
 }
+
+/// Short marker of pattern options shown before the pattern: `L`iteral or `R`egex followed by case `s`ensitive,
+/// `i`nsensitive or s`m`art. E.g. `Lm` for default literal smart-case pattern.
+pub(crate) fn pattern_marker(kind: PatternKind, case: CaseMode) -> String {
+    let kind = match kind {
+        PatternKind::Literal => 'L',
+        PatternKind::Regex => 'R',
+    };
+    let case = match case {
+        CaseMode::Sensitive => 's',
+        CaseMode::Insensitive => 'i',
+        CaseMode::Smart => 'm',
+    };
+    format!("{kind}{case}")
+}
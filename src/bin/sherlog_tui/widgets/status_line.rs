@@ -66,7 +66,8 @@ impl<'a> Widget for StatusLine<'a> {
                 ..area
             };
 
-            render_field(item, a, buf);
+            // Fields not fitting the line are cut
+            render_field(item, a.intersection(area), buf);
             cur += a.width + 1;
         }
 
@@ -99,7 +100,7 @@ impl RenderCursor for StatusLine<'_> {
 }
 
 fn render_field(text: Text<'_>, area: Rect, buf: &mut Buffer) {
-    if text.lines.get(0).is_none() {
+    if text.lines.is_empty() {
         return;
     }
     let first_line_spans = text.lines.into_iter().next().unwrap().0;
@@ -0,0 +1,141 @@
+use std::io::Read;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use log::debug;
+
+use crate::source::{Encoding, LineReader};
+
+/// Source of lines arriving while the log is already being viewed
+pub trait LineFeed {
+    /// Returns lines that arrived since the last call. Never blocks.
+    fn poll(&mut self) -> std::io::Result<Vec<FeedLine>>;
+
+    /// Tells that no more lines will arrive
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+/// Line delivered by a [`LineFeed`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedLine {
+    pub text: String,
+    /// Undecodable bytes were replaced
+    pub lossy: bool,
+    /// Marks an event of the feed itself (e.g. file rotation) rather than a logged line. Separator is a record of
+    /// its own and filters always keep it.
+    pub separator: bool,
+}
+
+impl FeedLine {
+    pub fn new(text: String, lossy: bool) -> Self {
+        FeedLine {
+            text,
+            lossy,
+            separator: false,
+        }
+    }
+
+    pub fn separator(text: String) -> Self {
+        FeedLine {
+            text,
+            lossy: false,
+            separator: true,
+        }
+    }
+}
+
+/// Maximal number of lines sent from reading thread at once
+const BATCH_SIZE: usize = 1024;
+
+/// Reads a stream (e.g. standard input) on a background thread, so lines can be displayed before the producer
+/// finishes.
+pub struct StreamReader {
+    receiver: Receiver<std::io::Result<Vec<(String, bool)>>>,
+    finished: bool,
+}
+
+impl StreamReader {
+    pub fn spawn<R: Read + Send + 'static>(reader: R, encoding: Option<Encoding>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            if let Err(e) = read_batches(reader, encoding, |batch| sender.send(Ok(batch)).is_ok()) {
+                // Receiver may be already gone, nothing to do about it then
+                let _ = sender.send(Err(e));
+            }
+        });
+        StreamReader {
+            receiver,
+            finished: false,
+        }
+    }
+}
+
+/// Reads lines in batches. Batch is flushed when it's full or when reading more would block.
+/// Stops early when `send` returns false.
+fn read_batches<R: Read, F: FnMut(Vec<(String, bool)>) -> bool>(
+    reader: R,
+    encoding: Option<Encoding>,
+    mut send: F,
+) -> std::io::Result<()> {
+    let mut reader = LineReader::new(reader, encoding)?;
+    let mut batch = Vec::new();
+    while let Some(line) = reader.next_line()? {
+        batch.push(line);
+        let flush = batch.len() >= BATCH_SIZE || !reader.has_buffered_data();
+        if flush && !send(std::mem::take(&mut batch)) {
+            return Ok(());
+        }
+    }
+    if !batch.is_empty() {
+        send(batch);
+    }
+    debug!("StreamReader reached end of stream");
+    Ok(())
+}
+
+impl LineFeed for StreamReader {
+    fn poll(&mut self) -> std::io::Result<Vec<FeedLine>> {
+        let mut lines = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(batch) => lines.extend(
+                    batch?
+                        .into_iter()
+                        .map(|(line, lossy)| FeedLine::new(line, lossy)),
+                ),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    break;
+                }
+            }
+        }
+        Ok(lines)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reads_whole_stream() {
+        let mut reader = StreamReader::spawn(&b"line1\nline\xff2\n"[..], None);
+        let mut lines = Vec::new();
+        while !reader.is_finished() {
+            lines.extend(reader.poll().unwrap());
+        }
+        assert_eq!(
+            lines,
+            vec![
+                FeedLine::new(String::from("line1"), false),
+                FeedLine::new(String::from("line\u{fffd}2"), true)
+            ]
+        );
+    }
+}
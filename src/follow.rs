@@ -0,0 +1,324 @@
+use std::borrow::Cow;
+use std::fs::{File, Metadata};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use log::info;
+
+use crate::feed::{FeedLine, LineFeed};
+use crate::source::MemorySource;
+
+/// Watches a growing file (like `tail -f`) and returns lines appended since the last poll.
+///
+/// Follower tracks the path, not the file handle. When the file gets rotated (path points to a different file) or
+/// truncated, reading restarts from the beginning of the new content and a separator line is emitted to mark the
+/// boundary. Truncation is recognized by the file getting shorter than what was read or by its first bytes changing,
+/// so a file truncated and rewritten past the read offset between two polls is not read from the middle.
+pub struct FileFollower {
+    path: PathBuf,
+    file: File,
+    file_id: Option<u64>,
+    offset: u64,
+    partial: Vec<u8>,
+    /// First bytes of the file, up to [`FINGERPRINT_LEN`] of the content already read
+    fingerprint: Vec<u8>,
+}
+
+/// Length of the file start compared on every poll to recognize rewritten content
+const FINGERPRINT_LEN: u64 = 256;
+
+impl FileFollower {
+    /// Starts following `path` from byte `offset`. Everything before the offset is considered already consumed.
+    pub fn new<P: AsRef<Path>>(path: P, offset: u64) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        let file_id = file_id(&file.metadata()?);
+        let mut follower = FileFollower {
+            path,
+            file,
+            file_id,
+            offset,
+            partial: Vec::new(),
+            fingerprint: Vec::new(),
+        };
+        follower.fingerprint = follower.read_start(offset.min(FINGERPRINT_LEN))?;
+        Ok(follower)
+    }
+
+    /// Reads complete lines at the end of the file, at most `max_len` bytes of them, and starts following the file
+    /// right after them. An unterminated last line is left to the follower, which delivers it once the writer
+    /// finishes it.
+    pub fn open<P: AsRef<Path>>(path: P, max_len: u64) -> std::io::Result<(MemorySource, Self)> {
+        let mut file = File::open(&path)?;
+        let len = file.metadata()?.len();
+        let start = len.saturating_sub(max_len);
+        file.seek(SeekFrom::Start(start))?;
+        let mut content = Vec::new();
+        file.take(len - start).read_to_end(&mut content)?;
+        let complete_len = memchr::memrchr(b'\n', &content).map_or(0, |pos| pos + 1);
+        // Tail starting in the middle of a line begins with the next one
+        let first = match start {
+            0 => 0,
+            _ => memchr::memchr(b'\n', &content).map_or(0, |pos| pos + 1),
+        };
+        let source =
+            MemorySource::from_reader(&content[first.min(complete_len)..complete_len], None)?;
+        let offset = start + complete_len as u64;
+        Ok((source, FileFollower::new(path, offset)?))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read_new_lines(&mut self) -> std::io::Result<Vec<FeedLine>> {
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let read = self.file.read_to_end(&mut self.partial)?;
+        self.offset += read as u64;
+        if (self.fingerprint.len() as u64) < FINGERPRINT_LEN.min(self.offset) {
+            self.fingerprint = self.read_start(self.offset.min(FINGERPRINT_LEN))?;
+        }
+        Ok(self.take_complete_lines())
+    }
+
+    /// Reads up to `len` bytes from the file start
+    fn read_start(&mut self, len: u64) -> std::io::Result<Vec<u8>> {
+        let mut start = Vec::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        (&mut self.file).take(len).read_to_end(&mut start)?;
+        Ok(start)
+    }
+
+    /// Tells if the file got shorter than what was read or its start no longer matches the fingerprint
+    fn is_truncated(&mut self) -> std::io::Result<bool> {
+        if self.file.metadata()?.len() < self.offset {
+            return Ok(true);
+        }
+        let start = self.read_start(self.fingerprint.len() as u64)?;
+        Ok(start != self.fingerprint)
+    }
+
+    /// Tells if the path points to a different file than the one being read.
+    /// Missing path is not a rotation yet - new file may not have been created.
+    fn is_rotated(&self) -> bool {
+        match std::fs::metadata(&self.path) {
+            Ok(metadata) => file_id(&metadata) != self.file_id,
+            Err(_) => false,
+        }
+    }
+
+    fn reopen(&mut self) -> std::io::Result<()> {
+        self.file = File::open(&self.path)?;
+        self.file_id = file_id(&self.file.metadata()?);
+        self.offset = 0;
+        self.fingerprint.clear();
+        Ok(())
+    }
+
+    /// Takes all buffered lines, including the unterminated one
+    fn take_remaining_lines(&mut self) -> Vec<FeedLine> {
+        let mut lines = self.take_complete_lines();
+        if !self.partial.is_empty() {
+            lines.push(decode_line(&std::mem::take(&mut self.partial)));
+        }
+        lines
+    }
+
+    fn take_complete_lines(&mut self) -> Vec<FeedLine> {
+        let complete_len = match memchr::memrchr(b'\n', &self.partial) {
+            Some(pos) => pos + 1,
+            None => return Vec::new(),
+        };
+        let rest = self.partial.split_off(complete_len);
+        let complete = std::mem::replace(&mut self.partial, rest);
+        // Complete data always ends with a newline, so the last split element is empty
+        let mut lines: Vec<_> = complete.split(|&b| b == b'\n').map(decode_line).collect();
+        lines.pop();
+        lines
+    }
+}
+
+impl LineFeed for FileFollower {
+    /// Returns complete lines appended to the file since the last call.
+    ///
+    /// A trailing line without newline is held back until it is terminated.
+    fn poll(&mut self) -> std::io::Result<Vec<FeedLine>> {
+        if self.is_truncated()? {
+            info!("{} truncated", self.path.display());
+            let mut lines = self.take_remaining_lines();
+            lines.push(separator_line(&self.path, "truncated"));
+            self.offset = 0;
+            self.fingerprint.clear();
+            lines.extend(self.read_new_lines()?);
+            return Ok(lines);
+        }
+
+        let mut lines = self.read_new_lines()?;
+        if self.is_rotated() {
+            info!("{} rotated", self.path.display());
+            // Old file is fully read now, the rest of the data comes from the file that took its place
+            lines.extend(self.take_remaining_lines());
+            lines.push(separator_line(&self.path, "rotated"));
+            self.reopen()?;
+            lines.extend(self.read_new_lines()?);
+        }
+        Ok(lines)
+    }
+}
+
+fn separator_line(path: &Path, event: &str) -> FeedLine {
+    FeedLine::separator(format!("--- {} {event} ---", path.display()))
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/// Decodes line as UTF-8, marking it lossy if invalid bytes were replaced
+fn decode_line(line: &[u8]) -> FeedLine {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    match String::from_utf8_lossy(line) {
+        Cow::Borrowed(line) => FeedLine::new(String::from(line), false),
+        Cow::Owned(line) => FeedLine::new(line, true),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+    use crate::LineSource;
+
+    /// Polls line texts, dropping their flags
+    fn poll(follower: &mut FileFollower) -> Vec<String> {
+        let lines = follower.poll().unwrap();
+        lines.into_iter().map(|line| line.text).collect()
+    }
+
+    #[test]
+    fn returns_appended_lines() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"old line\n").unwrap();
+        let mut follower = FileFollower::new(file.path(), 9).unwrap();
+        assert!(poll(&mut follower).is_empty());
+
+        file.write_all(b"new line 1\r\nnew line 2\n").unwrap();
+        assert_eq!(poll(&mut follower), vec!["new line 1", "new line 2"]);
+        assert!(poll(&mut follower).is_empty());
+
+        file.write_all(b"bad \xff\n").unwrap();
+        assert_eq!(
+            follower.poll().unwrap(),
+            vec![FeedLine::new(String::from("bad \u{fffd}"), true)]
+        );
+    }
+
+    #[test]
+    fn holds_back_unterminated_line() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let mut follower = FileFollower::new(file.path(), 0).unwrap();
+
+        file.write_all(b"complete\npart").unwrap();
+        assert_eq!(poll(&mut follower), vec!["complete"]);
+
+        file.write_all(b"ial\n").unwrap();
+        assert_eq!(poll(&mut follower), vec!["partial"]);
+    }
+
+    #[test]
+    fn completes_unterminated_line_read_on_open() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"a\nb").unwrap();
+        let (source, mut follower) = FileFollower::open(file.path(), u64::MAX).unwrap();
+        assert_eq!(source.line_count(), 1);
+        assert!(poll(&mut follower).is_empty());
+
+        file.write_all(b"c\n").unwrap();
+        assert_eq!(poll(&mut follower), vec!["bc"]);
+    }
+
+    #[test]
+    fn reads_only_tail_on_open() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"first\nsecond\nthird\n").unwrap();
+        let (source, mut follower) = FileFollower::open(file.path(), 9).unwrap();
+        assert_eq!(source.line_count(), 1);
+        assert_eq!(source.line(0), Some("third"));
+
+        file.write_all(b"fourth\n").unwrap();
+        assert_eq!(poll(&mut follower), vec!["fourth"]);
+    }
+
+    #[test]
+    fn restarts_after_truncation() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let mut follower = FileFollower::new(file.path(), 0).unwrap();
+        file.write_all(b"before truncation\n").unwrap();
+        assert_eq!(poll(&mut follower), vec!["before truncation"]);
+
+        file.as_file().set_len(0).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.write_all(b"after\n").unwrap();
+
+        let separator = format!("--- {} truncated ---", file.path().display());
+        assert_eq!(
+            follower.poll().unwrap(),
+            vec![
+                FeedLine::separator(separator),
+                FeedLine::new(String::from("after"), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn restarts_when_rewritten_past_offset() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"old 1\n").unwrap();
+        let mut follower = FileFollower::new(file.path(), 6).unwrap();
+
+        file.as_file().set_len(0).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.write_all(b"new 1\nnew 2\n").unwrap();
+
+        let separator = format!("--- {} truncated ---", file.path().display());
+        assert_eq!(
+            poll(&mut follower),
+            vec![separator.as_str(), "new 1", "new 2"]
+        );
+        assert!(poll(&mut follower).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn switches_to_new_file_after_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        std::fs::write(&path, "first\n").unwrap();
+        let mut follower = FileFollower::new(&path, 0).unwrap();
+        assert_eq!(poll(&mut follower), vec!["first"]);
+
+        // Late write to the rotated file must not be lost
+        let mut old = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        std::fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        old.write_all(b"late").unwrap();
+        std::fs::write(&path, "second\n").unwrap();
+
+        let separator = format!("--- {} rotated ---", path.display());
+        assert_eq!(
+            poll(&mut follower),
+            vec!["late", separator.as_str(), "second"]
+        );
+        assert!(poll(&mut follower).is_empty());
+    }
+}
@@ -1,67 +1,499 @@
+mod feed;
+mod follow;
+pub mod parse;
+mod process;
+mod query;
+pub mod source;
+mod timestamp;
 mod ty;
 
+use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+use std::path::Path;
 
+pub use feed::{FeedLine, LineFeed, StreamReader};
+pub use follow::FileFollower;
 use log::debug;
+pub use parse::{
+    detect_parser, Fields, JournalExportReader, JsonParser, LineParser, LogFormat, LogfmtParser,
+    RegexParser, SyslogParser,
+};
+pub use process::{ChildProcess, ProcessState};
+pub use query::{Query, QueryError};
 pub use regex::Regex;
-pub use ty::filter::RegexFilter;
+pub use source::{Compression, Encoding, LineSource, MemorySource, MergedSource, MmapSource};
+use timestamp::TimestampDetector;
+pub use timestamp::{
+    parse_duration, DetectedTimestampFormat, TimeSpec, TimeWindow, Timestamp, TimestampFormat,
+};
+pub use ty::filter::{
+    Comparison, ContextLines, FieldFilter, FieldPredicate, Filter, FilterInput, RegexFilter,
+    TimeFilter,
+};
+pub use ty::level::Level;
+pub use ty::pattern::{CaseMode, Pattern, PatternKind};
+pub use ty::record::RecordStart;
 pub use ty::span::{SpanKind, SpanRef};
 pub use ty::text::{TextLine, TextLineRef};
 
 pub struct Sherlog {
-    lines: Vec<String>,
-    filters: Vec<RegexFilter>,
+    source: Box<dyn LineSource>,
+    filters: Vec<Filter>,
+    search: Option<Regex>,
     highlight: Option<Regex>,
     index_filtered: BTreeSet<usize>,
+    /// Lines in [`Sherlog::index_filtered`] only as context of lines passing filters
+    index_context: BTreeSet<usize>,
+    context: ContextLines,
     index_search: BTreeMap<usize, Vec<(u32, u32)>>,
+    /// Lines of level [`MIN_INDEXED_LEVEL`] and above. Built on the first level jump, see
+    /// [`Sherlog::next_level_line`].
+    index_level: Option<BTreeMap<usize, Level>>,
+    /// Origin of every line, see [`Sherlog::append_from`]. Empty as long as all lines come from origin 0.
+    origins: Vec<u16>,
+    /// Lines marking events of their feed, see [`FeedLine::separator`]
+    separators: BTreeSet<usize>,
+    /// Timestamp format detection of every origin. Origins keep detecting on append until enough of their lines are
+    /// sampled.
+    timestamp_detectors: Vec<TimestampDetector>,
+    /// Parser candidates, see [`Sherlog::detect_parser`]. Only the parser given to [`Sherlog::set_parser`] when set
+    /// explicitly.
+    parsers: Vec<Box<dyn LineParser>>,
+    /// Index of the parser in use
+    parser: Option<usize>,
+    /// Parser detection is repeated on append until enough lines are sampled
+    detecting_parser: bool,
+    record_start: RecordStart,
+    /// First line of the last record, kept up to date while filtering appended lines
+    last_record_start: usize,
 }
 
 impl Sherlog {
     pub fn new(text: &str) -> Self {
-        let lines: Vec<_> = text.lines().map(String::from).collect();
-        let index_filtered = (0..lines.len()).collect();
-        Sherlog {
-            lines,
+        Sherlog::with_source(MemorySource::new(text))
+    }
+
+    /// Opens log file without reading it into memory when possible. See [`source::open_file`].
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Sherlog::open_with_encoding(path, None)
+    }
+
+    pub fn open_with_encoding<P: AsRef<Path>>(
+        path: P,
+        encoding: Option<Encoding>,
+    ) -> std::io::Result<Self> {
+        source::open_file(path, encoding).map(Sherlog::with_source)
+    }
+
+    pub fn with_source<S: LineSource + 'static>(source: S) -> Self {
+        let index_filtered = (0..source.line_count()).collect();
+        let mut detector = TimestampDetector::new();
+        detector.sample_lines((0..source.line_count()).filter_map(|n| source.line(n)));
+        let mut sherlog = Sherlog {
+            source: Box::new(source),
             filters: Vec::new(),
+            search: None,
             highlight: None,
             index_filtered,
+            index_context: BTreeSet::new(),
+            context: ContextLines::default(),
             index_search: BTreeMap::new(),
+            index_level: None,
+            origins: Vec::new(),
+            separators: BTreeSet::new(),
+            timestamp_detectors: vec![detector],
+            parsers: Vec::new(),
+            parser: None,
+            detecting_parser: false,
+            record_start: RecordStart::default(),
+            last_record_start: 0,
+        };
+        sherlog.detect_parser(
+            LogFormat::DETECTABLE
+                .iter()
+                .map(LogFormat::parser)
+                .collect(),
+        );
+        sherlog
+    }
+
+    /// Merges several logs into a single timeline, see [`MergedSource`]. Origin of each line is the index of the
+    /// log it comes from.
+    pub fn merge(sources: Vec<Box<dyn LineSource>>) -> Self {
+        let merged = MergedSource::new(sources);
+        let origins = (0..merged.line_count())
+            .map(|n| merged.source_of(n).unwrap_or_default())
+            .collect();
+        let timestamp_detectors = merged.timestamp_detectors().to_vec();
+        let mut sherlog = Sherlog::with_source(merged);
+        sherlog.origins = origins;
+        sherlog.timestamp_detectors = timestamp_detectors;
+        sherlog
+    }
+
+    /// Sets parser used to extract [`Fields`] of structured log lines, ending format detection. Field filters are
+    /// re-applied.
+    pub fn set_parser(&mut self, parser: Option<Box<dyn LineParser>>) {
+        self.parser = parser.is_some().then_some(0);
+        self.parsers = parser.into_iter().collect();
+        self.detecting_parser = false;
+        self.on_parser_changed();
+    }
+
+    /// Picks the parser reading the first lines best, see [`parse::detect_parser`]. Built-in formats are detected on
+    /// construction. Until enough lines are available (e.g. when streaming) detection is repeated on append.
+    /// Returns name of the detected format.
+    pub fn detect_parser(&mut self, candidates: Vec<Box<dyn LineParser>>) -> Option<&str> {
+        self.parsers = candidates;
+        self.parser = None;
+        self.detecting_parser = true;
+        self.run_parser_detection();
+        self.parser_name()
+    }
+
+    /// Name of the format lines are parsed as
+    pub fn parser_name(&self) -> Option<&str> {
+        self.parser().map(LineParser::name)
+    }
+
+    /// Fields of line `n`. `None` without parser or when the line is not in parser's format.
+    pub fn fields(&self, n: usize) -> Option<Fields> {
+        self.parser()?.parse(self.source.line(n)?)
+    }
+
+    fn parser(&self) -> Option<&dyn LineParser> {
+        self.parsers.get(self.parser?).map(|p| p.as_ref())
+    }
+
+    fn run_parser_detection(&mut self) {
+        let detected = parse::detect_parser(&self.parsers, self.lines().map(|(_, line)| line));
+        let sampled = self
+            .lines()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .take(parse::DETECT_SAMPLE_LINES)
+            .count();
+        if sampled >= parse::DETECT_SAMPLE_LINES {
+            self.detecting_parser = false;
+        }
+        if detected != self.parser {
+            self.parser = detected;
+            self.on_parser_changed();
         }
     }
 
-    pub fn filter(&mut self, filters: Vec<RegexFilter>) {
-        self.filters = filters;
-        let filtered_lines: BTreeSet<_> = self
-            .lines
+    fn on_parser_changed(&mut self) {
+        // Levels may come from parsed fields
+        self.index_level = None;
+        if self
+            .filters
             .iter()
-            .enumerate()
-            .filter(|(_, line)| self.filters.iter().all(|pat| pat.is_match(line)))
-            .map(|(n, _)| n)
-            .collect();
+            .any(|f| f.needs_fields() || f.needs_level())
+        {
+            let filters = std::mem::take(&mut self.filters);
+            self.filter(filters);
+        }
+    }
+
+    /// Sets how lines group into records and re-applies filters
+    pub fn set_record_start(&mut self, record_start: RecordStart) {
+        self.record_start = record_start;
+        let filters = std::mem::take(&mut self.filters);
+        self.filter(filters);
+    }
+
+    /// Keeps records passing all `filters`. A record passes or fails as a whole with all its lines, see
+    /// [`RecordStart`]. Context lines set before are kept around them.
+    pub fn filter(&mut self, filters: Vec<Filter>) {
+        self.filter_with_context(filters, self.context)
+    }
+
+    /// Keeps records passing all `filters` together with `context` lines around every line of them
+    pub fn filter_with_context(&mut self, filters: Vec<Filter>, context: ContextLines) {
+        self.filters = filters;
+        self.context = context;
+        self.index_context = BTreeSet::new();
+        if self.filters.is_empty() {
+            self.index_filtered = (0..self.line_count()).collect();
+            return;
+        }
+        let needs_timestamp = self.filters.iter().any(Filter::needs_timestamp);
+        let needs_level = self.filters.iter().any(Filter::needs_level);
+        let mut record_timestamp = None;
+        let mut record_level = None;
+        let mut is_record_filtered_in = |record: &[(usize, &str)]| {
+            let (n, head) = record[0];
+            if needs_timestamp {
+                // Records without timestamp belong to the preceding timestamped record
+                record_timestamp = self.parse_timestamp(n, head).or(record_timestamp);
+            }
+            if needs_level {
+                record_level = self.next_record_level(n, head, record_level);
+            }
+            self.is_filtered_in(record, record_timestamp, record_level)
+        };
+
+        let mut filtered_lines = BTreeSet::new();
+        let mut record = Vec::new();
+        for (n, line) in self.lines() {
+            if !record.is_empty() && !self.continues_record(n, line) {
+                if is_record_filtered_in(&record) {
+                    filtered_lines.extend(record.iter().map(|(n, _)| *n));
+                }
+                record.clear();
+            }
+            record.push((n, line));
+        }
+        if !record.is_empty() && is_record_filtered_in(&record) {
+            filtered_lines.extend(record.iter().map(|(n, _)| *n));
+        }
+        self.last_record_start = record.first().map_or(0, |(n, _)| *n);
+        self.index_context = self.context_of(&filtered_lines);
+        filtered_lines.extend(&self.index_context);
         self.index_filtered = filtered_lines;
     }
 
+    /// Lines around `matched` ones within [`ContextLines`], not matched themselves
+    fn context_of(&self, matched: &BTreeSet<usize>) -> BTreeSet<usize> {
+        if self.context == ContextLines::default() {
+            return BTreeSet::new();
+        }
+        let mut context = BTreeSet::new();
+        // Lines before `covered` were visited already, so every line is visited once whatever the context size
+        let mut covered = 0;
+        for n in matched.iter().filter(|n| !self.separators.contains(n)) {
+            let start = n.saturating_sub(self.context.before).max(covered);
+            let end = n
+                .saturating_add(self.context.after)
+                .saturating_add(1)
+                .min(self.line_count());
+            context.extend((start..end).filter(|n| !matched.contains(n)));
+            covered = covered.max(end);
+        }
+        context
+    }
+
+    /// Re-evaluates which lines of `window` are shown as context, after lines passing filters changed
+    fn update_context(&mut self, window: Range<usize>) {
+        if self.context == ContextLines::default() {
+            return;
+        }
+        for n in window {
+            if self.index_filtered.contains(&n) && !self.index_context.contains(&n) {
+                continue;
+            }
+            let near = n.saturating_sub(self.context.after)..=n.saturating_add(self.context.before);
+            let is_context = self
+                .index_filtered
+                .range(near)
+                .any(|m| !self.index_context.contains(m) && !self.separators.contains(m));
+            if is_context {
+                self.index_filtered.insert(n);
+                self.index_context.insert(n);
+            } else {
+                self.index_filtered.remove(&n);
+                self.index_context.remove(&n);
+            }
+        }
+    }
+
     pub fn search(&mut self, pattern: Option<Regex>) {
         match pattern {
             Some(pattern) => self.do_search(pattern),
             None => {
                 self.index_search = BTreeMap::new();
+                self.search = None;
                 self.highlight = None;
             }
         }
     }
 
-    fn do_search(&mut self, pattern: Regex) {
-        self.index_search = BTreeMap::new();
-        // Builds search results index
-        for (n, line) in self.lines.iter().enumerate() {
-            for found in pattern.find_iter(line) {
-                self.index_search
-                    .entry(n)
-                    .or_default()
-                    .push((found.start() as u32, found.end() as u32))
+    /// Appends new lines at the end of the log. Filter and search indexes are updated only for the new lines.
+    pub fn append<I: IntoIterator<Item = String>>(&mut self, lines: I) {
+        self.append_from(0, lines.into_iter().map(|line| FeedLine::new(line, false)))
+    }
+
+    /// Appends lines delivered by [`LineFeed::poll`] marking them as coming from `origin`.
+    ///
+    /// Origin is an opaque tag chosen by the frontend (e.g. a stream or file index) and returned with every
+    /// [`TextLineRef`]. Lines of the initial source have origin 0.
+    pub fn append_from<I: IntoIterator<Item = FeedLine>>(&mut self, origin: u16, lines: I) {
+        let mut timestamps_changed = false;
+        for FeedLine {
+            text: line,
+            lossy,
+            separator,
+        } in lines
+        {
+            let n = self.source.line_count();
+            if separator {
+                self.separators.insert(n);
+            }
+            if origin != 0 && self.origins.len() < n {
+                self.origins.resize(n, 0);
+            }
+            if !self.origins.is_empty() || origin != 0 {
+                self.origins.push(origin);
+            }
+            timestamps_changed |= self.detect_timestamp_format(origin, &line);
+            if let Some(found) = self.search.as_ref().and_then(|p| find_matches(p, &line)) {
+                self.index_search.insert(n, found);
+            }
+            if self.index_level.is_some() {
+                let level = self.line_level(&line).filter(|l| *l >= MIN_INDEXED_LEVEL);
+                if let Some((index, level)) = self.index_level.as_mut().zip(level) {
+                    index.insert(n, level);
+                }
+            }
+            if !self.continues_record(n, &line) {
+                // Record before is complete, so it is filtered only once
+                self.filter_record(self.last_record_start..n);
+                self.last_record_start = n;
+            }
+            self.source.push_line(line, lossy);
+        }
+        if timestamps_changed && !self.filters.is_empty() {
+            // Records are delimited by timestamps, so they may have changed
+            let filters = std::mem::take(&mut self.filters);
+            self.filter(filters);
+        } else {
+            // Last record may still grow, it is filtered again when the next one starts
+            self.filter_record(self.last_record_start..self.line_count());
+        }
+        if self.detecting_parser {
+            self.run_parser_detection();
+        }
+    }
+
+    /// Samples line of `origin` if its timestamp format is still being detected. Tells if the format changed.
+    fn detect_timestamp_format(&mut self, origin: u16, line: &str) -> bool {
+        let origin = origin as usize;
+        if self.timestamp_detectors.len() <= origin {
+            self.timestamp_detectors
+                .resize(origin + 1, TimestampDetector::new());
+        }
+        let detector = &mut self.timestamp_detectors[origin];
+        if detector.is_done() {
+            return false;
+        }
+        let before = detector.format().map(|f| f.format);
+        detector.sample(line);
+        detector.format().map(|f| f.format) != before
+    }
+
+    /// Tells if line `n` continues the record of the preceding line, see [`RecordStart`].
+    ///
+    /// Records are cut at every [`MAX_RECORD_LINES`]-th line, so the start of any record is at most that many lines
+    /// back, wherever the scan starts.
+    fn continues_record(&self, n: usize, line: &str) -> bool {
+        if n.is_multiple_of(MAX_RECORD_LINES)
+            || self.origin(n) != self.origin(n - 1)
+            || self.separators.contains(&n)
+            || self.separators.contains(&(n - 1))
+        {
+            return false;
+        }
+        match &self.record_start {
+            RecordStart::Auto => match self.detected_timestamp_format(self.origin(n)) {
+                Some(format) => format.parse(line).is_none(),
+                None => line.starts_with([' ', '\t']),
+            },
+            RecordStart::Pattern(pattern) => !pattern.is_match(line),
+            RecordStart::Line => false,
+        }
+    }
+
+    /// Physical lines of the record line `n` belongs to
+    pub fn record(&self, n: usize) -> Range<usize> {
+        let continues = |m: usize| {
+            self.source
+                .line(m)
+                .is_some_and(|line| self.continues_record(m, line))
+        };
+        let start = (0..=n).rev().find(|m| !continues(*m)).unwrap_or(0);
+        let end = (n + 1..self.line_count())
+            .find(|m| !continues(*m))
+            .unwrap_or(self.line_count());
+        start..end
+    }
+
+    /// Updates filtered index with the record spanning `lines`
+    fn filter_record(&mut self, lines: Range<usize>) {
+        if lines.is_empty() {
+            return;
+        }
+        let is_filtered_in = self.filters.is_empty() || {
+            let timestamp = if self.filters.iter().any(Filter::needs_timestamp) {
+                self.record_timestamp(lines.start)
+            } else {
+                None
+            };
+            let level = if self.filters.iter().any(Filter::needs_level) {
+                self.record_level(lines.start)
+            } else {
+                None
+            };
+            let record: Vec<_> = lines
+                .clone()
+                .filter_map(|n| Some((n, self.source.line(n)?)))
+                .collect();
+            self.is_filtered_in(&record, timestamp, level)
+        };
+        for n in lines.clone() {
+            self.index_context.remove(&n);
+            if is_filtered_in {
+                self.index_filtered.insert(n);
+            } else {
+                self.index_filtered.remove(&n);
             }
         }
+        self.update_context(lines.start.saturating_sub(self.context.before)..lines.end);
+    }
+
+    /// Tells if the record made of `lines` passes filters. Text filters see record lines joined with `\n`, fields are
+    /// those of the first line. Separators always pass.
+    fn is_filtered_in(
+        &self,
+        lines: &[(usize, &str)],
+        timestamp: Option<Timestamp>,
+        level: Option<Level>,
+    ) -> bool {
+        let Some(&(n, head)) = lines.first() else {
+            return false;
+        };
+        if self.separators.contains(&n) {
+            return true;
+        }
+        let fields = match self.parser() {
+            Some(parser) if self.filters.iter().any(Filter::needs_fields) => parser.parse(head),
+            _ => None,
+        };
+        let text = match lines {
+            [_] => Cow::Borrowed(head),
+            _ => Cow::Owned(
+                lines
+                    .iter()
+                    .map(|(_, line)| *line)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        };
+        let input = FilterInput {
+            text: &text,
+            timestamp,
+            fields: fields.as_ref(),
+            level,
+        };
+        self.filters.iter().all(|f| f.is_match(&input))
+    }
+
+    fn do_search(&mut self, pattern: Regex) {
+        // Builds search results index
+        self.index_search = self
+            .lines()
+            .filter_map(|(n, line)| find_matches(&pattern, line).map(|found| (n, found)))
+            .collect();
+        self.search = Some(pattern.clone());
         // For now we force highlight to search pattern as current API cannot handle overlapping search result
         // and highlight. And we do want to mark search spans.
         self.highlight = Some(pattern);
@@ -85,17 +517,55 @@ impl Sherlog {
             .map(|i| *i.0)
     }
 
+    /// First line passing filters at or after `start` whose own level is at least `min`. Only warnings and more
+    /// severe lines are indexed, so lower `min` finds warnings.
+    pub fn next_level_line(&mut self, start: usize, min: Level) -> Option<usize> {
+        self.build_level_index();
+        self.index_level
+            .as_ref()?
+            .range(start..)
+            .find(|(n, level)| **level >= min && self.index_filtered.contains(n))
+            .map(|(n, _)| *n)
+    }
+
+    /// Last line passing filters at or before `start` whose own level is at least `min`, see
+    /// [`Sherlog::next_level_line`]
+    pub fn prev_level_line(&mut self, start: usize, min: Level) -> Option<usize> {
+        self.build_level_index();
+        self.index_level
+            .as_ref()?
+            .range(..=start)
+            .rev()
+            .find(|(n, level)| **level >= min && self.index_filtered.contains(n))
+            .map(|(n, _)| *n)
+    }
+
+    fn build_level_index(&mut self) {
+        if self.index_level.is_none() {
+            let index = self
+                .lines()
+                .filter_map(|(n, line)| Some((n, self.line_level(line)?)))
+                .filter(|(_, level)| *level >= MIN_INDEXED_LEVEL)
+                .collect();
+            self.index_level = Some(index);
+        }
+    }
+
     pub fn highlight(&mut self, highlight: Option<Regex>) {
         self.highlight = highlight
     }
 
-    pub fn get_lines(&self, first: usize, cnt: Option<usize>) -> Vec<TextLineRef> {
+    pub fn get_lines(&self, first: usize, cnt: Option<usize>) -> Vec<TextLineRef<'_>> {
         debug!("get_lines - first: {first} cnt: {cnt:?}");
         let lines: Vec<_> = self
             .index_filtered
             .range(first..)
             .take(cnt.unwrap_or(usize::MAX))
-            .filter_map(|n| self.lines.get(*n).map(|line| self.make_text_line(*n, line)))
+            .filter_map(|n| {
+                self.source
+                    .line(*n)
+                    .map(|line| self.make_text_line(*n, line))
+            })
             .collect();
 
         log_returned_lines("get_lines", lines.as_slice());
@@ -103,7 +573,7 @@ impl Sherlog {
         lines
     }
 
-    pub fn get_lines_rev(&self, last: usize, cnt: Option<usize>) -> Vec<TextLineRef> {
+    pub fn get_lines_rev(&self, last: usize, cnt: Option<usize>) -> Vec<TextLineRef<'_>> {
         debug!("get_lines_rev - last: {last} cnt: {cnt:?}");
         // Seems that we cannot get last cnt elements from BTreeSet::Range.
         // We need to double reverse and to do so we need to store intermediate processed data.
@@ -117,7 +587,11 @@ impl Sherlog {
         let lines: Vec<_> = reversed
             .into_iter()
             .rev()
-            .filter_map(|n| self.lines.get(*n).map(|line| self.make_text_line(*n, line)))
+            .filter_map(|n| {
+                self.source
+                    .line(*n)
+                    .map(|line| self.make_text_line(*n, line))
+            })
             .collect();
 
         log_returned_lines("get_lines_rev", lines.as_slice());
@@ -125,7 +599,7 @@ impl Sherlog {
     }
 
     fn make_text_line<'a>(&'a self, n: usize, line: &'a str) -> TextLineRef<'a> {
-        if let Some(pattern) = &self.highlight {
+        let spans = if let Some(pattern) = &self.highlight {
             let mut pos = 0;
             let mut spans = Vec::new();
             for m in pattern.find_iter(line) {
@@ -136,14 +610,147 @@ impl Sherlog {
             if pos != line.len() {
                 spans.push(SpanRef::raw(&line[pos..]));
             }
-            TextLineRef { line_num: n, spans }
+            spans
         } else {
-            TextLineRef::raw(n, line)
+            vec![SpanRef::raw(line)]
+        };
+        TextLineRef {
+            line_num: n,
+            spans,
+            lossy: self.source.is_lossy(n),
+            origin: self.origin(n),
+            timestamp: self.parse_timestamp(n, line),
+            level: self.line_level(line),
+            context: self.index_context.contains(&n),
         }
     }
 
+    /// Normalized timestamp of line `n`, parsed with the format detected for its origin
+    pub fn timestamp(&self, n: usize) -> Option<Timestamp> {
+        self.source
+            .line(n)
+            .and_then(|line| self.parse_timestamp(n, line))
+    }
+
+    fn parse_timestamp(&self, n: usize, line: &str) -> Option<Timestamp> {
+        self.detected_timestamp_format(self.origin(n))?.parse(line)
+    }
+
+    /// Timestamp of line `n` or, for lines without one (e.g. stack traces), of the closest preceding line having it
+    pub fn record_timestamp(&self, n: usize) -> Option<Timestamp> {
+        (n.saturating_sub(MAX_RECORD_LINES)..=n)
+            .rev()
+            .find_map(|n| self.timestamp(n))
+    }
+
+    /// Level of line `n` taken from the `level` field (or similar, see [`parse::LEVEL_FIELDS`]) of the parsed line, or
+    /// found in the line text, see [`Level::detect`]
+    pub fn level(&self, n: usize) -> Option<Level> {
+        self.line_level(self.source.line(n)?)
+    }
+
+    fn line_level(&self, line: &str) -> Option<Level> {
+        let from_fields = self
+            .parser()
+            .and_then(|p| p.parse(line))
+            .and_then(|fields| {
+                parse::LEVEL_FIELDS
+                    .iter()
+                    .find_map(|name| fields.get(*name)?.parse().ok())
+            });
+        from_fields.or_else(|| Level::detect(line))
+    }
+
+    /// Level of line `n` or, for lines without level and timestamp (e.g. stack traces), of the record started by the
+    /// closest preceding line having any of them
+    pub fn record_level(&self, n: usize) -> Option<Level> {
+        (n.saturating_sub(MAX_RECORD_LINES)..=n)
+            .rev()
+            .filter_map(|n| Some((n, self.source.line(n)?)))
+            .find_map(|(n, line)| match self.line_level(line) {
+                Some(level) => Some(Some(level)),
+                None => self.parse_timestamp(n, line).map(|_| None),
+            })
+            .flatten()
+    }
+
+    /// Level of the record line `n` belongs to, given the level of the record preceding line belongs to
+    fn next_record_level(&self, n: usize, line: &str, previous: Option<Level>) -> Option<Level> {
+        match self.line_level(line) {
+            Some(level) => Some(level),
+            None if self.parse_timestamp(n, line).is_some() => None,
+            None => previous,
+        }
+    }
+
+    /// First line passing current filters with timestamp at or after `time`.
+    ///
+    /// Uses binary search, so assumes timestamps grow through the log. Lines without timestamp are treated as if
+    /// they had the timestamp of the closest preceding line.
+    pub fn find_time(&self, time: Timestamp) -> Option<usize> {
+        let is_before = |n| self.record_timestamp(n).is_none_or(|t| t < time);
+        let (mut low, mut high) = (0, self.line_count());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if is_before(mid) {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        self.index_filtered.range(low..).next().copied()
+    }
+
+    /// Last timestamp in the log
+    pub fn last_timestamp(&self) -> Option<Timestamp> {
+        self.record_timestamp(self.line_count().checked_sub(1)?)
+    }
+
+    /// Timestamp format detected for lines of given origin
+    pub fn timestamp_format(&self, origin: u16) -> Option<TimestampFormat> {
+        self.detected_timestamp_format(origin).map(|f| f.format)
+    }
+
+    fn detected_timestamp_format(&self, origin: u16) -> Option<DetectedTimestampFormat> {
+        self.timestamp_detectors.get(origin as usize)?.format()
+    }
+
+    /// Origin of line `n`. See [`Sherlog::append_from`].
+    pub fn origin(&self, n: usize) -> u16 {
+        self.origins.get(n).copied().unwrap_or_default()
+    }
+
     pub fn line_count(&self) -> usize {
-        self.lines.len()
+        self.source.line_count()
+    }
+
+    /// Number of the last line that passes current filters
+    pub fn last_line_num(&self) -> Option<usize> {
+        self.index_filtered.last().copied()
+    }
+
+    fn lines(&self) -> impl Iterator<Item = (usize, &str)> + '_ {
+        (0..self.source.line_count()).filter_map(|n| self.source.line(n).map(|line| (n, line)))
+    }
+}
+
+/// Least severe level indexed for level jumps. Indexing more common levels would cost memory for no use, nearly
+/// every line would match.
+const MIN_INDEXED_LEVEL: Level = Level::Warn;
+
+/// Longest record, see [`Sherlog::record`]. Also limits how far back [`Sherlog::record_timestamp`] looks for a line
+/// with timestamp.
+const MAX_RECORD_LINES: usize = 1000;
+
+fn find_matches(pattern: &Regex, line: &str) -> Option<Vec<(u32, u32)>> {
+    let found: Vec<_> = pattern
+        .find_iter(line)
+        .map(|m| (m.start() as u32, m.end() as u32))
+        .collect();
+    if found.is_empty() {
+        None
+    } else {
+        Some(found)
     }
 }
 
@@ -282,4 +889,370 @@ mod test {
         assert_eq!(sherlog.prev_search_result(1), Some(1));
         assert_eq!(sherlog.prev_search_result(0), None);
     }
+
+    #[test]
+    fn can_append_filtered_and_searched() {
+        let mut sherlog = Sherlog::new("line1\nline2\n");
+        sherlog.filter(vec!["line[23]".try_into().unwrap()]);
+        sherlog.search(Some(Regex::new("3").unwrap()));
+
+        sherlog.append(vec![String::from("line3"), String::from("line4")]);
+
+        assert_eq!(sherlog.line_count(), 4);
+        assert_eq!(sherlog.last_line_num(), Some(2));
+        assert_eq!(
+            as_strings(sherlog.get_lines(0, None)),
+            vec![String::from("line2"), String::from("line3")]
+        );
+        assert_eq!(sherlog.next_search_result(0), Some(2));
+    }
+
+    #[test]
+    fn keeps_origin_of_appended_lines() {
+        let mut sherlog = Sherlog::new("line1\n");
+        sherlog.append_from(1, vec![FeedLine::new(String::from("line2\u{fffd}"), true)]);
+        sherlog.append(vec![String::from("line3")]);
+
+        let origins: Vec<_> = sherlog
+            .get_lines(0, None)
+            .iter()
+            .map(|l| l.origin)
+            .collect();
+        assert_eq!(origins, vec![0, 1, 0]);
+        let lossy: Vec<_> = sherlog.get_lines(0, None).iter().map(|l| l.lossy).collect();
+        assert_eq!(lossy, vec![false, true, false]);
+    }
+
+    #[test]
+    fn provides_line_timestamps() {
+        let mut sherlog = Sherlog::new("Mar 14 08:15:02 host app: started\n  details\n");
+        sherlog.append_from(
+            1,
+            vec![FeedLine::new(
+                String::from("1678781702 other stream"),
+                false,
+            )],
+        );
+
+        assert_eq!(sherlog.timestamp_format(0), Some(TimestampFormat::Syslog));
+        assert_eq!(
+            sherlog.timestamp_format(1),
+            Some(TimestampFormat::EpochSeconds)
+        );
+        let timestamps: Vec<_> = sherlog
+            .get_lines(0, None)
+            .iter()
+            .map(|l| l.timestamp.map(|t| t.timestamp()))
+            .collect();
+        assert_eq!(timestamps[1], None);
+        assert_eq!(timestamps[2], Some(1678781702));
+        assert!(timestamps[0].is_some());
+    }
+
+    #[test]
+    fn finds_first_line_at_time() {
+        let data = "2023-01-01 10:00:00 a\n  trace\n2023-01-01 10:05:00 b\nno timestamp\n2023-01-01 10:10:00 c\n";
+        let mut sherlog = Sherlog::new(data);
+        let time = |s: &str| s.parse::<TimeSpec>().unwrap().resolve(None).unwrap();
+
+        assert_eq!(sherlog.find_time(time("2023-01-01 09:00:00")), Some(0));
+        assert_eq!(sherlog.find_time(time("2023-01-01 10:00:01")), Some(2));
+        assert_eq!(sherlog.find_time(time("2023-01-01 10:05:00")), Some(2));
+        assert_eq!(sherlog.find_time(time("2023-01-01 10:05:01")), Some(4));
+        assert_eq!(sherlog.find_time(time("2023-01-01 11:00:00")), None);
+        assert_eq!(sherlog.record_timestamp(3), sherlog.timestamp(2));
+
+        sherlog.filter(vec!["c".try_into().unwrap()]);
+        assert_eq!(sherlog.find_time(time("2023-01-01 10:00:01")), Some(4));
+    }
+
+    #[test]
+    fn can_filter_by_time() {
+        let data = "2023-01-01 10:00:00 a\n2023-01-01 10:05:00 b\n  trace\n2023-01-01 10:10:00 c\n";
+        let mut sherlog = Sherlog::new(data);
+        let window: TimeWindow = "last 5m".parse().unwrap();
+        let filter = window.resolve(None, sherlog.last_timestamp()).unwrap();
+        sherlog.filter(vec![filter.into(), "a|b|c|trace".try_into().unwrap()]);
+
+        assert_eq!(
+            as_strings(sherlog.get_lines(0, None)),
+            vec!["2023-01-01 10:05:00 b", "  trace", "2023-01-01 10:10:00 c"]
+        );
+
+        sherlog.append(vec![String::from("  trace2")]);
+        assert_eq!(sherlog.last_line_num(), Some(4));
+    }
+
+    #[test]
+    fn provides_fields_of_parsed_lines() {
+        let mut sherlog = Sherlog::new("{\"msg\":\"hi\",\"a\":{\"b\":1}}\nplain\n");
+        assert_eq!(sherlog.fields(0), None);
+
+        sherlog.set_parser(Some(LogFormat::Json.parser()));
+        let line_num = sherlog.get_lines(0, Some(1))[0].line_num;
+        let fields = sherlog.fields(line_num).unwrap();
+        assert_eq!(fields["msg"], "hi");
+        assert_eq!(fields["a.b"], "1");
+        assert_eq!(sherlog.fields(1), None);
+    }
+
+    #[test]
+    fn can_filter_by_field() {
+        let data =
+            "{\"status\":200,\"msg\":\"ok\"}\n{\"status\":503,\"msg\":\"down\"}\nstatus 500\n";
+        let mut sherlog = Sherlog::new(data);
+        sherlog.set_parser(None);
+        let filter: FieldFilter = "status >= 500".parse().unwrap();
+        sherlog.filter(vec![filter.into()]);
+        assert!(sherlog.get_lines(0, None).is_empty());
+
+        sherlog.set_parser(Some(LogFormat::Json.parser()));
+        assert_eq!(
+            as_strings(sherlog.get_lines(0, None)),
+            vec!["{\"status\":503,\"msg\":\"down\"}"]
+        );
+
+        sherlog.append(vec![String::from("{\"status\":\"502\"}")]);
+        assert_eq!(sherlog.get_lines(0, None).len(), 2);
+    }
+
+    #[test]
+    fn detects_format_of_appended_lines() {
+        let mut sherlog = Sherlog::new("");
+        assert_eq!(sherlog.parser_name(), None);
+
+        sherlog.append(vec![
+            String::from("plain"),
+            String::from("level=info msg=hi"),
+        ]);
+        assert_eq!(sherlog.parser_name(), None);
+        sherlog.append(vec![String::from("level=warn msg=\"slow query\"")]);
+        assert_eq!(sherlog.parser_name(), Some("logfmt"));
+        assert_eq!(sherlog.fields(2).unwrap()["msg"], "slow query");
+
+        let sherlog =
+            Sherlog::new("<34>Oct 11 22:14:15 host su: failed\nOct 11 22:14:16 host su: ok\n");
+        assert_eq!(sherlog.parser_name(), Some("syslog"));
+    }
+
+    #[test]
+    fn can_filter_by_level() {
+        let data = "2023-01-01 10:00:00 INFO start\n2023-01-01 10:00:01 ERROR failed\n  at main\n\
+            2023-01-01 10:00:02 no level\n  detail\n2023-01-01 10:00:03 [warn] slow\n";
+        let mut sherlog = Sherlog::new(data);
+        assert_eq!(sherlog.level(1), Some(Level::Error));
+        assert_eq!(sherlog.level(2), None);
+        assert_eq!(sherlog.record_level(2), Some(Level::Error));
+        assert_eq!(sherlog.record_level(4), None);
+
+        sherlog.filter(vec![Filter::MinLevel(Level::Warn)]);
+        let lines: Vec<_> = sherlog
+            .get_lines(0, None)
+            .iter()
+            .map(|l| l.line_num)
+            .collect();
+        assert_eq!(lines, vec![1, 2, 3, 4, 5]);
+
+        sherlog.append(vec![
+            String::from("  more"),
+            String::from("2023-01-01 10:00:04 DEBUG x"),
+        ]);
+        assert_eq!(sherlog.last_line_num(), Some(6));
+        assert_eq!(sherlog.get_lines(0, None)[4].level, Some(Level::Warn));
+
+        let mut sherlog = Sherlog::new("{\"level\":\"debug\"}\n{\"level\":\"error\"}\n");
+        sherlog.set_parser(None);
+        sherlog.filter(vec![Filter::MinLevel(Level::Warn)]);
+        assert_eq!(sherlog.get_lines(0, None).len(), 2);
+        sherlog.set_parser(Some(LogFormat::Json.parser()));
+        assert_eq!(sherlog.get_lines(0, None)[0].line_num, 1);
+    }
+
+    #[test]
+    fn jumps_between_levels() {
+        let data = "INFO a\nWARN b\nERROR c\n  at x\nWARN d\nINFO e\n";
+        let mut sherlog = Sherlog::new(data);
+        assert_eq!(sherlog.next_level_line(0, Level::Warn), Some(1));
+        assert_eq!(sherlog.next_level_line(2, Level::Warn), Some(2));
+        assert_eq!(sherlog.next_level_line(3, Level::Error), None);
+        assert_eq!(sherlog.prev_level_line(5, Level::Error), Some(2));
+        assert_eq!(sherlog.prev_level_line(0, Level::Warn), None);
+
+        sherlog.filter(vec!["b|d|f".try_into().unwrap()]);
+        assert_eq!(sherlog.next_level_line(2, Level::Warn), Some(4));
+
+        sherlog.append(vec![String::from("FATAL f")]);
+        assert_eq!(sherlog.next_level_line(5, Level::Error), Some(6));
+    }
+
+    #[test]
+    fn filters_whole_records() {
+        let data = "2023-01-01 10:00:00 INFO start\n2023-01-01 10:00:01 ERROR failed\n\
+            java.io.IOException: closed\n\tat Main.run\n2023-01-01 10:00:02 INFO done\n";
+        let mut sherlog = Sherlog::new(data);
+        assert_eq!(sherlog.record(3), 1..4);
+        assert_eq!(sherlog.record(4), 4..5);
+
+        sherlog.filter(vec!["Exception".try_into().unwrap()]);
+        assert_eq!(
+            as_strings(sherlog.get_lines(0, None)),
+            vec![
+                "2023-01-01 10:00:01 ERROR failed",
+                "java.io.IOException: closed",
+                "\tat Main.run"
+            ]
+        );
+
+        sherlog.append(vec![
+            String::from("2023-01-01 10:00:03 WARN retry"),
+            String::from("Caused by: java.net.SocketException"),
+        ]);
+        assert_eq!(sherlog.last_line_num(), Some(6));
+        assert_eq!(sherlog.get_lines(0, None).len(), 5);
+
+        sherlog.set_record_start(" 10:00:0[0-2] ".parse().unwrap());
+        assert_eq!(sherlog.record(6), 4..7);
+        assert_eq!(sherlog.get_lines(0, None)[3].line_num, 4);
+    }
+
+    #[test]
+    fn filters_records_growing_over_appends() {
+        let mut sherlog = Sherlog::new("2023-01-01 10:00:00 INFO start\n");
+        sherlog.filter(vec!["Exception".try_into().unwrap()]);
+        sherlog.append(vec![String::from("2023-01-01 10:00:01 ERROR failed")]);
+        assert_eq!(sherlog.get_lines(0, None).len(), 0);
+
+        sherlog.append(vec![String::from("java.io.IOException: closed")]);
+        assert_eq!(sherlog.get_lines(0, None).len(), 2);
+
+        sherlog.append(vec![String::from("2023-01-01 10:00:02 INFO done")]);
+        assert_eq!(
+            as_strings(sherlog.get_lines(0, None)),
+            vec![
+                "2023-01-01 10:00:01 ERROR failed",
+                "java.io.IOException: closed"
+            ]
+        );
+    }
+
+    #[test]
+    fn cuts_long_records() {
+        let data = format!("head\n{}", "  more\n".repeat(1500));
+        let mut sherlog = Sherlog::new(&data);
+        assert_eq!(sherlog.record(0), 0..MAX_RECORD_LINES);
+        assert_eq!(sherlog.record(MAX_RECORD_LINES - 1), 0..MAX_RECORD_LINES);
+        assert_eq!(sherlog.record(MAX_RECORD_LINES), MAX_RECORD_LINES..1501);
+
+        sherlog.filter(vec!["head".try_into().unwrap()]);
+        assert_eq!(sherlog.get_lines(0, None).len(), sherlog.record(0).len());
+        assert_eq!(sherlog.last_line_num(), Some(MAX_RECORD_LINES - 1));
+    }
+
+    #[test]
+    fn ignores_stray_timestamps_when_grouping() {
+        let output = "running tests\n1678781702 seed\ntest a ... ok\ntest b ... FAIL\n";
+        let mut sherlog = Sherlog::new(output);
+        assert_eq!(sherlog.timestamp_format(0), None);
+        sherlog.filter(vec!["FAIL".try_into().unwrap()]);
+        assert_eq!(
+            as_strings(sherlog.get_lines(0, None)),
+            vec!["test b ... FAIL"]
+        );
+
+        let mut sherlog = Sherlog::new("");
+        sherlog.filter(vec!["FAIL".try_into().unwrap()]);
+        sherlog.append(output.lines().skip(1).map(String::from));
+        assert_eq!(sherlog.record(2), 2..3);
+        assert_eq!(
+            as_strings(sherlog.get_lines(0, None)),
+            vec!["test b ... FAIL"]
+        );
+    }
+
+    #[test]
+    fn keeps_separators_as_own_records() {
+        let mut sherlog = Sherlog::new("2023-01-01 10:00:00 ERROR failed\n");
+        sherlog.filter(vec!["failed".try_into().unwrap()]);
+        sherlog.append_from(
+            0,
+            vec![
+                FeedLine::separator(String::from("--- app.log rotated ---")),
+                FeedLine::new(String::from("  failed to continue"), false),
+                FeedLine::new(String::from("2023-01-01 10:00:01 INFO ok"), false),
+            ],
+        );
+        assert_eq!(sherlog.record(0), 0..1);
+        assert_eq!(sherlog.record(1), 1..2);
+        assert_eq!(sherlog.record(2), 2..3);
+        let lines: Vec<_> = sherlog
+            .get_lines(0, None)
+            .iter()
+            .map(|l| l.line_num)
+            .collect();
+        assert_eq!(lines, vec![0, 1, 2]);
+
+        sherlog.filter(vec!["INFO".try_into().unwrap()]);
+        assert_eq!(
+            as_strings(sherlog.get_lines(0, None)),
+            vec!["--- app.log rotated ---", "2023-01-01 10:00:01 INFO ok"]
+        );
+    }
+
+    #[test]
+    fn shows_context_lines() {
+        let mut sherlog = Sherlog::new("a\nb\ntimeout\nc\nd\ne\nf\ntimeout\ng\n");
+        let context = ContextLines {
+            before: 1,
+            after: 2,
+        };
+        sherlog.filter_with_context(vec!["timeout".try_into().unwrap()], context);
+        let lines: Vec<_> = sherlog
+            .get_lines(0, None)
+            .iter()
+            .map(|l| (l.line_num, l.context))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (1, true),
+                (2, false),
+                (3, true),
+                (4, true),
+                (6, true),
+                (7, false),
+                (8, true)
+            ]
+        );
+
+        sherlog.append(vec![String::from("h"), String::from("i")]);
+        assert_eq!(sherlog.last_line_num(), Some(9));
+        sherlog.append(vec![String::from("timeout")]);
+        assert_eq!(sherlog.get_lines(10, None)[0].line_num, 10);
+        assert!(sherlog.get_lines_rev(11, Some(2))[0].context);
+
+        sherlog.filter(vec!["c|d".try_into().unwrap()]);
+        assert_eq!(sherlog.get_lines(0, None).len(), 5);
+        let context = ContextLines {
+            before: 0,
+            after: usize::MAX,
+        };
+        sherlog.filter_with_context(vec!["d".try_into().unwrap()], context);
+        assert_eq!(sherlog.get_lines(0, None)[0].line_num, 4);
+        assert_eq!(sherlog.last_line_num(), Some(11));
+        sherlog.filter_with_context(Vec::new(), ContextLines::default());
+        assert!(sherlog.get_lines(0, None).iter().all(|l| !l.context));
+    }
+
+    #[test]
+    fn merged_lines_keep_their_origin() {
+        let sherlog = Sherlog::merge(vec![
+            Box::new(MemorySource::new("2023-01-01 10:00:01 a\n")),
+            Box::new(MemorySource::new("2023-01-01 10:00:00 b\n")),
+        ]);
+
+        let lines = sherlog.get_lines(0, None);
+        assert_eq!(lines[0].to_string(), "2023-01-01 10:00:00 b");
+        assert_eq!(lines[0].origin, 1);
+        assert_eq!(lines[1].origin, 0);
+    }
 }
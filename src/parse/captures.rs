@@ -0,0 +1,88 @@
+use regex::Regex;
+
+use super::{Fields, LineParser};
+
+/// Parses bespoke text formats with a regex whose named capture groups become fields, e.g.
+/// `(?P<ts>\S+) \[(?P<level>\w+)\] (?P<msg>.*)`.
+///
+/// Groups not taking part in the match are left out. Lines not matching the regex are not in the format.
+#[derive(Clone, Debug)]
+pub struct RegexParser {
+    name: String,
+    regex: Regex,
+}
+
+impl RegexParser {
+    /// Fails when the pattern is not a valid regex or has no named group
+    pub fn new(name: &str, pattern: &str) -> Result<Self, String> {
+        let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+        if regex.capture_names().flatten().next().is_none() {
+            return Err(format!("no named capture group in {pattern}"));
+        }
+        Ok(RegexParser {
+            name: String::from(name),
+            regex,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.regex.as_str()
+    }
+}
+
+impl LineParser for RegexParser {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn parse(&self, line: &str) -> Option<Fields> {
+        let captures = self.regex.captures(line)?;
+        let fields = self
+            .regex
+            .capture_names()
+            .flatten()
+            .filter_map(|name| {
+                Some((
+                    String::from(name),
+                    String::from(captures.name(name)?.as_str()),
+                ))
+            })
+            .collect();
+        Some(fields)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn extracts_named_groups() {
+        let parser = RegexParser::new(
+            "app",
+            r"(?P<ts>\S+) \[(?P<level>\w+)\] (?:(?P<user>\w+): )?(?P<msg>.*)",
+        )
+        .unwrap();
+
+        let fields = parser
+            .parse("2023-03-14T08:15:02Z [WARN] disk almost full")
+            .unwrap();
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("level", "WARN"),
+                ("msg", "disk almost full"),
+                ("ts", "2023-03-14T08:15:02Z")
+            ]
+        );
+        assert_eq!(parser.parse("10:00 [INFO] bob: hi").unwrap()["user"], "bob");
+        assert_eq!(parser.parse("no brackets here"), None);
+
+        assert!(RegexParser::new("app", r"(\S+) (.*)").is_err());
+        assert!(RegexParser::new("app", r"(?P<a>").is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use std::io::{BufRead, BufReader, Read};
+
+use chrono::DateTime;
+
+/// Converts `journalctl -o export` output into RFC 5424 syslog lines readable by [`super::SyslogParser`], one per
+/// journal entry:
+///
+/// `<30>1 2023-03-14T08:15:02.123456Z host sshd 1234 - [journal _SYSTEMD_UNIT="sshd.service"] Accepted key`
+///
+/// Application fields go to the `journal` structured data element together with `_SYSTEMD_UNIT`. Other trusted
+/// (`_`) and address (`__`) fields are dropped. Multi-line messages continue on lines indented by two spaces.
+pub struct JournalExportReader<R> {
+    reader: BufReader<R>,
+    /// Converted entry being read out
+    pending: Vec<u8>,
+    pos: usize,
+}
+
+/// Fields making the syslog header instead of structured data
+const HEADER_FIELDS: [&str; 9] = [
+    "MESSAGE",
+    "PRIORITY",
+    "SYSLOG_FACILITY",
+    "SYSLOG_IDENTIFIER",
+    "SYSLOG_PID",
+    "MESSAGE_ID",
+    "_HOSTNAME",
+    "_COMM",
+    "_PID",
+];
+
+impl<R: Read> JournalExportReader<R> {
+    pub fn new(reader: R) -> Self {
+        JournalExportReader {
+            reader: BufReader::new(reader),
+            pending: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Reads fields of the next entry. Entries are separated by an empty line.
+    fn read_entry(&mut self) -> std::io::Result<Option<Vec<(String, String)>>> {
+        let mut fields = Vec::new();
+        loop {
+            let mut line = Vec::new();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok((!fields.is_empty()).then_some(fields));
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            if line.is_empty() {
+                if fields.is_empty() {
+                    continue;
+                }
+                return Ok(Some(fields));
+            }
+
+            let (key, value) = match line.iter().position(|b| *b == b'=') {
+                Some(eq) => (line[..eq].to_vec(), line[eq + 1..].to_vec()),
+                None => {
+                    // Binary safe field: name, little endian 64 bit size, data and a newline
+                    let mut size = [0; 8];
+                    self.reader.read_exact(&mut size)?;
+                    let mut value = Vec::new();
+                    (&mut self.reader)
+                        .take(u64::from_le_bytes(size))
+                        .read_to_end(&mut value)?;
+                    self.reader.read_exact(&mut [0])?;
+                    (line, value)
+                }
+            };
+            fields.push((
+                String::from_utf8_lossy(&key).into_owned(),
+                String::from_utf8_lossy(&value).into_owned(),
+            ));
+        }
+    }
+}
+
+impl<R: Read> Read for JournalExportReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos >= self.pending.len() {
+            match self.read_entry()? {
+                Some(entry) => {
+                    self.pending = to_syslog_line(&entry).into_bytes();
+                    self.pos = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.pending.len() - self.pos);
+        buf[..len].copy_from_slice(&self.pending[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+fn to_syslog_line(fields: &[(String, String)]) -> String {
+    let get = |key: &str| {
+        fields
+            .iter()
+            .find(|(k, v)| k == key && !v.is_empty())
+            .map(|(_, v)| v.as_str())
+    };
+    let number = |key: &str| get(key).and_then(|v| v.parse::<u8>().ok());
+    let priority =
+        number("SYSLOG_FACILITY").unwrap_or(1) % 24 * 8 + number("PRIORITY").unwrap_or(6) % 8;
+    let timestamp = get("__REALTIME_TIMESTAMP")
+        .and_then(|t| DateTime::from_timestamp_micros(t.parse().ok()?))
+        .map(|t| t.format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string());
+    let header = |value: Option<&str>| String::from(value.map_or("-", |v| v.trim()));
+
+    let params: Vec<_> = fields
+        .iter()
+        .filter(|(k, _)| {
+            !HEADER_FIELDS.contains(&k.as_str()) && (!k.starts_with('_') || k == "_SYSTEMD_UNIT")
+        })
+        .map(|(k, v)| {
+            let value = v
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace(']', "\\]");
+            format!(" {k}=\"{}\"", value.replace('\n', " "))
+        })
+        .collect();
+    let data = if params.is_empty() {
+        String::from("-")
+    } else {
+        format!("[journal{}]", params.concat())
+    };
+
+    let mut line = format!(
+        "<{priority}>1 {} {} {} {} {} {data}",
+        header(timestamp.as_deref()),
+        header(get("_HOSTNAME")),
+        header(get("SYSLOG_IDENTIFIER").or(get("_COMM"))),
+        header(get("_PID").or(get("SYSLOG_PID"))),
+        header(get("MESSAGE_ID")),
+    );
+    let mut message = get("MESSAGE").unwrap_or_default().lines();
+    if let Some(first) = message.next() {
+        line.push(' ');
+        line.push_str(first);
+    }
+    for continuation in message {
+        line.push_str("\n  ");
+        line.push_str(continuation);
+    }
+    line.push('\n');
+    line
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{LineParser, SyslogParser};
+
+    #[test]
+    fn converts_entries_to_syslog() {
+        let mut export = b"__CURSOR=s=1\n__REALTIME_TIMESTAMP=1678781702123456\nPRIORITY=4\n_HOSTNAME=web1\n\
+            SYSLOG_IDENTIFIER=nginx\n_PID=42\n_SYSTEMD_UNIT=nginx.service\n_UID=0\nREQUEST_ID=a\"b\n\
+            MESSAGE=slow upstream\n\n_HOSTNAME=web1\n_COMM=kernel\nMESSAGE\n"
+            .to_vec();
+        export.extend(9u64.to_le_bytes());
+        export.extend(b"oops\nline\n\n");
+
+        let mut converted = String::new();
+        JournalExportReader::new(&export[..])
+            .read_to_string(&mut converted)
+            .unwrap();
+        let lines: Vec<_> = converted.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "<12>1 2023-03-14T08:15:02.123456Z web1 nginx 42 - [journal _SYSTEMD_UNIT=\"nginx.service\" REQUEST_ID=\"a\\\"b\"] slow upstream",
+                "<14>1 - web1 kernel - - - oops",
+                "  line",
+            ]
+        );
+
+        let fields = SyslogParser.parse(lines[0]).unwrap();
+        assert_eq!(fields["level"], "warn");
+        assert_eq!(fields["app"], "nginx");
+        assert_eq!(fields["sd.journal.REQUEST_ID"], "a\"b");
+    }
+}
@@ -0,0 +1,85 @@
+use serde_json::Value;
+
+use super::{Fields, LineParser};
+
+/// Parses lines holding a single JSON object (JSON Lines)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonParser;
+
+impl LineParser for JsonParser {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn parse(&self, line: &str) -> Option<Fields> {
+        let line = line.trim();
+        if !line.starts_with('{') {
+            return None;
+        }
+        match serde_json::from_str(line).ok()? {
+            Value::Object(object) => {
+                let mut fields = Fields::new();
+                for (key, value) in object {
+                    flatten(key, value, &mut fields);
+                }
+                Some(fields)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Stores `value` under `path`. Object and array members get their key or index appended to the path.
+fn flatten(path: String, value: Value, fields: &mut Fields) {
+    match value {
+        Value::Object(object) if !object.is_empty() => {
+            for (key, value) in object {
+                flatten(format!("{path}.{key}"), value, fields);
+            }
+        }
+        Value::Array(array) if !array.is_empty() => {
+            for (i, value) in array.into_iter().enumerate() {
+                flatten(format!("{path}.{i}"), value, fields);
+            }
+        }
+        Value::String(s) => {
+            fields.insert(path, s);
+        }
+        // Numbers, booleans, null and empty containers are kept in their JSON notation
+        value => {
+            fields.insert(path, value.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flattens_nested_fields() {
+        let line = r#"{"level":"info","http":{"status":200,"tags":["a","b"]},"ok":true,"err":null,"meta":{}}"#;
+        let fields = JsonParser.parse(line).unwrap();
+
+        let expected: Vec<_> = [
+            ("err", "null"),
+            ("http.status", "200"),
+            ("http.tags.0", "a"),
+            ("http.tags.1", "b"),
+            ("level", "info"),
+            ("meta", "{}"),
+            ("ok", "true"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (String::from(k), String::from(v)))
+        .collect();
+        assert_eq!(fields.into_iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn rejects_other_lines() {
+        assert_eq!(JsonParser.parse("plain text"), None);
+        assert_eq!(JsonParser.parse("{broken"), None);
+        assert_eq!(JsonParser.parse("[1, 2]"), None);
+    }
+}
@@ -0,0 +1,96 @@
+use super::{Fields, LineParser};
+
+/// Parses logfmt lines: `ts=2023-03-14T08:15:02Z level=info msg="request done" dur=12ms`.
+///
+/// Quoted values support `\"`, `\\`, `\n`, `\r` and `\t` escapes. Keys without value (`debug`) get empty value. Lines
+/// without any `key=value` pair are not logfmt.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogfmtParser;
+
+impl LineParser for LogfmtParser {
+    fn name(&self) -> &str {
+        "logfmt"
+    }
+
+    fn parse(&self, line: &str) -> Option<Fields> {
+        let mut fields = Fields::new();
+        let mut has_pair = false;
+        let mut chars = line.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+
+            let mut key = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+                if c == '"' {
+                    return None;
+                }
+                key.push(c);
+            }
+            if key.is_empty() {
+                // Value without a key
+                return None;
+            }
+
+            let mut value = String::new();
+            if chars.next_if_eq(&'=').is_some() {
+                has_pair = true;
+                if chars.next_if_eq(&'"').is_some() {
+                    loop {
+                        match chars.next()? {
+                            '"' => break,
+                            '\\' => value.push(match chars.next()? {
+                                'n' => '\n',
+                                'r' => '\r',
+                                't' => '\t',
+                                c => c,
+                            }),
+                            c => value.push(c),
+                        }
+                    }
+                } else {
+                    while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                        value.push(c);
+                    }
+                }
+            }
+            fields.insert(key, value);
+        }
+        has_pair.then_some(fields)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::test_util::{pairs, parse_pairs};
+
+    #[test]
+    fn parses_pairs() {
+        assert_eq!(
+            parse_pairs(
+                &LogfmtParser,
+                r#"ts=2023-03-14T08:15:02Z level=info msg="request \"done\"\n" dur=12ms empty= debug"#
+            ),
+            pairs(&[
+                ("debug", ""),
+                ("dur", "12ms"),
+                ("empty", ""),
+                ("level", "info"),
+                ("msg", "request \"done\"\n"),
+                ("ts", "2023-03-14T08:15:02Z"),
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_other_lines() {
+        assert_eq!(parse_pairs(&LogfmtParser, "plain text line"), None);
+        assert_eq!(parse_pairs(&LogfmtParser, r#"msg="unterminated"#), None);
+        assert_eq!(parse_pairs(&LogfmtParser, r#"a=1 "quoted""#), None);
+        assert_eq!(parse_pairs(&LogfmtParser, "a=1 =2"), None);
+        assert_eq!(parse_pairs(&LogfmtParser, ""), None);
+    }
+}
@@ -0,0 +1,171 @@
+//! Parsers turning log lines into structured [`Fields`]. Raw line text is kept for display, fields are extracted
+//! on demand.
+
+mod captures;
+mod journal;
+mod json;
+mod logfmt;
+mod syslog;
+mod text;
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+pub use captures::RegexParser;
+pub use journal::JournalExportReader;
+pub use json::JsonParser;
+pub use logfmt::LogfmtParser;
+pub use syslog::SyslogParser;
+pub use text::TextParser;
+
+/// Fields of a structured log line by name. Nested keys are flattened to dotted paths (`http.status`).
+pub type Fields = BTreeMap<String, String>;
+
+pub trait LineParser {
+    /// Name of the format, as shown to the user
+    fn name(&self) -> &str;
+
+    /// Extracts fields of the line. `None` when the line is not in the parser's format.
+    fn parse(&self, line: &str) -> Option<Fields>;
+}
+
+/// Structured log formats with a built-in parser
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// JSON object per line
+    Json,
+    /// `key=value` pairs
+    Logfmt,
+    /// RFC 5424 or RFC 3164 syslog
+    Syslog,
+    /// Plain text lines starting with a timestamp
+    Text,
+    /// `journalctl -o export` output. Input has to be converted with [`JournalExportReader`] first, lines are then
+    /// parsed as syslog.
+    Journal,
+}
+
+impl LogFormat {
+    pub const ALL: [LogFormat; 5] = [
+        LogFormat::Json,
+        LogFormat::Syslog,
+        LogFormat::Logfmt,
+        LogFormat::Text,
+        LogFormat::Journal,
+    ];
+
+    /// Formats recognizable from line content in detection priority order. Journal export is not line based.
+    pub const DETECTABLE: [LogFormat; 4] = [
+        LogFormat::Json,
+        LogFormat::Syslog,
+        LogFormat::Logfmt,
+        LogFormat::Text,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogFormat::Json => "json",
+            LogFormat::Logfmt => "logfmt",
+            LogFormat::Syslog => "syslog",
+            LogFormat::Text => "text",
+            LogFormat::Journal => "journal",
+        }
+    }
+
+    pub fn parser(&self) -> Box<dyn LineParser> {
+        match self {
+            LogFormat::Json => Box::new(JsonParser),
+            LogFormat::Logfmt => Box::new(LogfmtParser),
+            LogFormat::Syslog | LogFormat::Journal => Box::new(SyslogParser),
+            LogFormat::Text => Box::new(TextParser),
+        }
+    }
+}
+
+/// Names of the field holding line level in common formats, by priority
+pub const LEVEL_FIELDS: [&str; 7] = [
+    "level",
+    "severity",
+    "lvl",
+    "loglevel",
+    "log.level",
+    "levelname",
+    "level_name",
+];
+
+/// Number of non-empty lines inspected when detecting the format of a log
+pub(crate) const DETECT_SAMPLE_LINES: usize = 100;
+
+/// Picks the parser reading most of the first non-empty lines and returns its index. The parser has to read more
+/// than half of them. On equal count the parser listed first wins.
+pub fn detect_parser<'a, I: IntoIterator<Item = &'a str>>(
+    parsers: &[Box<dyn LineParser>],
+    lines: I,
+) -> Option<usize> {
+    let mut hits = vec![0usize; parsers.len()];
+    let mut sampled = 0;
+    for line in lines
+        .into_iter()
+        .filter(|l| !l.trim().is_empty())
+        .take(DETECT_SAMPLE_LINES)
+    {
+        sampled += 1;
+        for (i, parser) in parsers.iter().enumerate() {
+            if parser.parse(line).is_some() {
+                hits[i] += 1;
+            }
+        }
+    }
+    let (best, count) = hits
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|(_, count)| **count)?;
+    (*count * 2 > sampled).then_some(best)
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LogFormat::ALL
+            .into_iter()
+            .find(|f| f.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = LogFormat::ALL.iter().map(LogFormat::as_str).collect();
+                format!(
+                    "unsupported format: {s} (expected one of {})",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Helpers comparing parsed fields in parser tests
+#[cfg(test)]
+pub(crate) mod test_util {
+    use super::LineParser;
+
+    /// Fields of `line` as (name, value) pairs ordered by name
+    pub fn parse_pairs(parser: &dyn LineParser, line: &str) -> Option<Vec<(String, String)>> {
+        parser
+            .parse(line)
+            .map(|fields| fields.into_iter().collect())
+    }
+
+    pub fn pairs(pairs: &[(&str, &str)]) -> Option<Vec<(String, String)>> {
+        Some(
+            pairs
+                .iter()
+                .map(|(k, v)| (String::from(*k), String::from(*v)))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,245 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use super::{Fields, LineParser};
+use crate::Level;
+
+/// Parses syslog lines in RFC 5424 (`<34>1 2023-03-14T08:15:02Z host app 42 ID47 [id k="v"] message`) or RFC 3164
+/// (`<34>Mar 14 08:15:02 host app[42]: message`, priority optional as in files written by syslog daemons) format.
+///
+/// Produces `timestamp`, `host`, `app`, `pid`, `msgid` and `message` fields as written in the line. Priority gives
+/// `facility` and `severity` names and the common `level`. Structured data parameters become `sd.<id>.<name>`.
+/// Nil values (`-`) are left out.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SyslogParser;
+
+impl LineParser for SyslogParser {
+    fn name(&self) -> &str {
+        "syslog"
+    }
+
+    fn parse(&self, line: &str) -> Option<Fields> {
+        parse_rfc5424(line).or_else(|| parse_rfc3164(line))
+    }
+}
+
+const FACILITIES: [&str; 24] = [
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+
+const SEVERITIES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+/// Splits `<PRI>` off the line
+fn split_priority(line: &str) -> Option<(u8, &str)> {
+    let (priority, rest) = line.strip_prefix('<')?.split_once('>')?;
+    if priority.is_empty() || priority.len() > 3 {
+        return None;
+    }
+    let priority: u8 = priority.parse().ok()?;
+    (usize::from(priority) < FACILITIES.len() * SEVERITIES.len()).then_some((priority, rest))
+}
+
+fn insert_priority(fields: &mut Fields, priority: u8) {
+    let severity = priority % 8;
+    fields.insert(
+        String::from("facility"),
+        String::from(FACILITIES[usize::from(priority / 8)]),
+    );
+    fields.insert(
+        String::from("severity"),
+        String::from(SEVERITIES[usize::from(severity)]),
+    );
+    if let Some(level) = Level::from_syslog_severity(severity) {
+        fields.insert(String::from("level"), String::from(level.as_str()));
+    }
+}
+
+fn insert_value(fields: &mut Fields, name: &str, value: &str) {
+    if !value.is_empty() && value != "-" {
+        fields.insert(String::from(name), String::from(value));
+    }
+}
+
+fn parse_rfc5424(line: &str) -> Option<Fields> {
+    let (priority, rest) = split_priority(line)?;
+    let mut parts = rest.strip_prefix("1 ")?.splitn(6, ' ');
+    let mut fields = Fields::new();
+    insert_priority(&mut fields, priority);
+    for name in ["timestamp", "host", "app", "pid", "msgid"] {
+        insert_value(&mut fields, name, parts.next()?);
+    }
+
+    let message = parse_structured_data(parts.next().unwrap_or_default(), &mut fields)?;
+    let message = message.strip_prefix(' ').unwrap_or(message);
+    insert_value(
+        &mut fields,
+        "message",
+        message.trim_start_matches('\u{feff}'),
+    );
+    Some(fields)
+}
+
+/// Stores structured data elements (`[id name="value" ...]...` or `-`) in fields, returns the rest of the line
+fn parse_structured_data<'a>(data: &'a str, fields: &mut Fields) -> Option<&'a str> {
+    if data.is_empty() {
+        return Some(data);
+    }
+    if let Some(rest) = data.strip_prefix('-') {
+        return Some(rest);
+    }
+
+    let mut rest = data.strip_prefix('[')?;
+    loop {
+        let id_len = rest.find([' ', ']'])?;
+        let id = &rest[..id_len];
+        rest = &rest[id_len..];
+        while let Some(param) = rest.strip_prefix(' ') {
+            let (name, value) = param.split_once("=\"")?;
+            let mut unescaped = String::new();
+            let mut chars = value.char_indices();
+            let end = loop {
+                match chars.next()? {
+                    (i, '"') => break i,
+                    (_, '\\') => match chars.next()? {
+                        (_, c @ ('"' | '\\' | ']')) => unescaped.push(c),
+                        (_, c) => {
+                            unescaped.push('\\');
+                            unescaped.push(c);
+                        }
+                    },
+                    (_, c) => unescaped.push(c),
+                }
+            };
+            fields.insert(format!("sd.{id}.{name}"), unescaped);
+            rest = &value[end + 1..];
+        }
+        rest = rest.strip_prefix(']')?;
+        match rest.strip_prefix('[') {
+            Some(next) => rest = next,
+            None => return Some(rest),
+        }
+    }
+}
+
+fn parse_rfc3164(line: &str) -> Option<Fields> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        Regex::new(
+            r"^(?:<(\d{1,3})>)?([A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}) (\S+) ([^\s\[:]+)(?:\[([^\]]*)\])?: ?(.*)$",
+        )
+        .unwrap()
+    });
+
+    let captures = pattern.captures(line)?;
+    let mut fields = Fields::new();
+    if let Some(priority) = captures.get(1) {
+        let (priority, _) = split_priority(&format!("<{}>", priority.as_str()))?;
+        insert_priority(&mut fields, priority);
+    }
+    for (i, name) in ["timestamp", "host", "app", "pid", "message"]
+        .into_iter()
+        .enumerate()
+    {
+        if let Some(value) = captures.get(i + 2) {
+            insert_value(&mut fields, name, value.as_str());
+        }
+    }
+    Some(fields)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::test_util::{pairs, parse_pairs};
+
+    #[test]
+    fn parses_rfc5424() {
+        assert_eq!(
+            parse_pairs(
+                &SyslogParser,
+                r#"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut="3" eventSource="App\"lication\]"][meta seq="1"] An application event"#
+            ),
+            pairs(&[
+                ("app", "evntslog"),
+                ("facility", "local4"),
+                ("host", "mymachine.example.com"),
+                ("level", "info"),
+                ("message", "An application event"),
+                ("msgid", "ID47"),
+                ("sd.exampleSDID@32473.eventSource", "App\"lication]"),
+                ("sd.exampleSDID@32473.iut", "3"),
+                ("sd.meta.seq", "1"),
+                ("severity", "notice"),
+                ("timestamp", "2003-10-11T22:14:15.003Z"),
+            ])
+        );
+        assert_eq!(
+            SyslogParser.parse("<11>1 - - su - - -").unwrap()["level"],
+            "error"
+        );
+        assert_eq!(
+            parse_pairs(&SyslogParser, "<11>1 - - su - - [unterminated"),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_rfc3164() {
+        assert_eq!(
+            parse_pairs(
+                &SyslogParser,
+                "<34>Oct 11 22:14:15 mymachine su[230]: 'su root' failed for lonvick on /dev/pts/8"
+            ),
+            pairs(&[
+                ("app", "su"),
+                ("facility", "auth"),
+                ("host", "mymachine"),
+                ("level", "fatal"),
+                ("message", "'su root' failed for lonvick on /dev/pts/8"),
+                ("pid", "230"),
+                ("severity", "crit"),
+                ("timestamp", "Oct 11 22:14:15"),
+            ])
+        );
+        assert_eq!(
+            parse_pairs(&SyslogParser, "Mar  4 08:15:02 host kernel: eth0 up"),
+            pairs(&[
+                ("app", "kernel"),
+                ("host", "host"),
+                ("message", "eth0 up"),
+                ("timestamp", "Mar  4 08:15:02"),
+            ])
+        );
+        assert_eq!(
+            parse_pairs(&SyslogParser, "<999>Oct 11 22:14:15 host su: x"),
+            None
+        );
+        assert_eq!(parse_pairs(&SyslogParser, "2023-03-14 plain line"), None);
+    }
+}
@@ -0,0 +1,45 @@
+use super::{Fields, LineParser};
+use crate::TimestampFormat;
+
+/// Parses plain text lines carrying a known timestamp near the line start (see [`TimestampFormat`]) into
+/// `timestamp` and `message` fields. Separators following the timestamp (`]`, `:`, `|`, `,`) are not part of the
+/// message.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TextParser;
+
+impl LineParser for TextParser {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    fn parse(&self, line: &str) -> Option<Fields> {
+        let range = TimestampFormat::ALL
+            .iter()
+            .find_map(|format| format.locate(line))?;
+        let message =
+            line[range.end..].trim_start_matches(|c: char| c.is_whitespace() || "]:|,".contains(c));
+        let mut fields = Fields::new();
+        fields.insert(String::from("timestamp"), String::from(&line[range]));
+        fields.insert(String::from("message"), String::from(message));
+        Some(fields)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn splits_timestamp_and_message() {
+        let fields = TextParser
+            .parse("[2023-03-14 08:15:02,125] INFO started")
+            .unwrap();
+        assert_eq!(fields["timestamp"], "2023-03-14 08:15:02,125");
+        assert_eq!(fields["message"], "INFO started");
+        assert_eq!(
+            TextParser.parse("1678781702 | tick").unwrap()["message"],
+            "tick"
+        );
+        assert_eq!(TextParser.parse("no timestamp"), None);
+    }
+}
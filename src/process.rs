@@ -0,0 +1,106 @@
+use std::fmt::Display;
+use std::process::{Child, Command, ExitStatus, Stdio};
+
+use log::{info, warn};
+
+use crate::source::Encoding;
+use crate::StreamReader;
+
+/// Process whose output is viewed as a log. Process is killed when dropped.
+pub struct ChildProcess {
+    child: Child,
+    state: ProcessState,
+}
+
+impl ChildProcess {
+    /// Spawns `program` with stdout and stderr captured. Returns the process with feeds for both streams.
+    pub fn spawn<S: AsRef<str>>(
+        program: &str,
+        args: &[S],
+        encoding: Option<Encoding>,
+    ) -> std::io::Result<(ChildProcess, StreamReader, StreamReader)> {
+        let mut child = Command::new(program)
+            .args(args.iter().map(AsRef::as_ref))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        info!("Spawned {program} (pid {})", child.id());
+
+        // Both are present since they were configured as piped
+        let stdout = StreamReader::spawn(child.stdout.take().unwrap(), encoding);
+        let stderr = StreamReader::spawn(child.stderr.take().unwrap(), encoding);
+        let process = ChildProcess {
+            child,
+            state: ProcessState::Running,
+        };
+        Ok((process, stdout, stderr))
+    }
+
+    /// Checks process state without blocking
+    pub fn state(&mut self) -> std::io::Result<ProcessState> {
+        if self.state == ProcessState::Running {
+            if let Some(status) = self.child.try_wait()? {
+                info!("Process {} finished: {status}", self.child.id());
+                self.state = ProcessState::Exited(status);
+            }
+        }
+        Ok(self.state)
+    }
+}
+
+impl Drop for ChildProcess {
+    fn drop(&mut self) {
+        if self.state == ProcessState::Running {
+            if let Err(e) = self.child.kill().and_then(|_| self.child.wait()) {
+                warn!("Cannot kill process {}: {e}", self.child.id());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessState {
+    Running,
+    Exited(ExitStatus),
+}
+
+impl Display for ProcessState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessState::Running => f.write_str("running"),
+            ProcessState::Exited(status) => match status.code() {
+                Some(code) => write!(f, "exited with {code}"),
+                // Killed by a signal on unix
+                None => write!(f, "{status}"),
+            },
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use crate::LineFeed;
+
+    fn read_all(feed: &mut StreamReader) -> Vec<String> {
+        let mut lines = Vec::new();
+        while !feed.is_finished() {
+            lines.extend(feed.poll().unwrap().into_iter().map(|line| line.text));
+        }
+        lines
+    }
+
+    #[test]
+    fn captures_streams_separately() {
+        let (mut process, mut stdout, mut stderr) =
+            ChildProcess::spawn("sh", &["-c", "echo out; echo err >&2; exit 3"], None).unwrap();
+
+        assert_eq!(read_all(&mut stdout), vec!["out"]);
+        assert_eq!(read_all(&mut stderr), vec!["err"]);
+        while process.state().unwrap() == ProcessState::Running {
+            std::thread::yield_now();
+        }
+        assert_eq!(process.state().unwrap().to_string(), "exited with 3");
+    }
+}
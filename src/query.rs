@@ -0,0 +1,483 @@
+//! Textual query language producing [`Filter`] trees.
+//!
+//! ```text
+//! query  := or
+//! or     := and ("OR" and)*
+//! and    := unary ("AND"? unary)*        adjacent terms are joined with AND
+//! unary  := "NOT" unary | "(" or ")" | term
+//! term   := /regex/ | "literal" | word | field
+//! field  := time(>|>=|<|<=)TIME | level(:|>=)NAME | FIELD(>|>=|<|<=)VALUE
+//! ```
+//!
+//! Words and quoted literals match as plain text. `level:NAME` matches lines of exactly that level, `level>=NAME`
+//! lines at least as severe (and lines without level), see [`crate::Level`]. Other names compare a parsed field with
+//! a number or level name, see [`crate::FieldFilter`].
+//!
+//! Example: `level:error AND NOT /health.*/ AND time>10:00`.
+
+use std::fmt::Display;
+
+use chrono::Duration;
+use regex::Regex;
+
+use crate::{FieldFilter, Filter, Level, RegexFilter, TimeFilter, TimeSpec, Timestamp};
+
+/// Parsed query, ready to be applied to [`crate::Sherlog`]
+#[derive(Clone, Debug)]
+pub struct Query {
+    pub filter: Filter,
+    /// Matches text terms that are not negated, for highlighting what the query looked for
+    pub highlight: Option<Regex>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryError {
+    /// Character position in the query where the problem was found
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl Query {
+    /// Parses `query`. Relative times and times of day refer to `current` timestamp.
+    pub fn parse(query: &str, current: Option<Timestamp>) -> Result<Query, QueryError> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: query.chars().count(),
+            current,
+            highlights: Vec::new(),
+        };
+        let filter = parser.parse_or(false)?;
+        match parser.peek() {
+            None => {}
+            Some(Token {
+                kind: TokenKind::RightParen,
+                position,
+            }) => return Err(error(*position, "unmatched )")),
+            Some(token) => return Err(error(token.position, "expected AND, OR or end of query")),
+        }
+        let highlight = match parser.highlights.as_slice() {
+            [] => None,
+            // Groups keep inline flags of a pattern from applying to the following ones
+            patterns => {
+                let groups: Vec<_> = patterns.iter().map(|p| format!("(?:{p})")).collect();
+                Regex::new(&groups.join("|")).ok()
+            }
+        };
+        Ok(Query { filter, highlight })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TokenKind {
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Not,
+    Regex(String),
+    Text(String),
+    Field {
+        name: String,
+        operator: String,
+        value: String,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    /// Character position of the token start
+    position: usize,
+}
+
+fn error(position: usize, message: impl Into<String>) -> QueryError {
+    QueryError {
+        position,
+        message: message.into(),
+    }
+}
+
+/// Names of terms not referring to parsed fields
+const FIELDS: [&str; 2] = ["time", "level"];
+
+/// Operators of field terms, longest first so `>=` is not taken for `>`
+const OPERATORS: [&str; 5] = [">=", "<=", ">", "<", ":"];
+
+fn tokenize(query: &str) -> Result<Vec<Token>, QueryError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let start = pos;
+        let kind = match chars[pos] {
+            c if c.is_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            '(' => {
+                pos += 1;
+                TokenKind::LeftParen
+            }
+            ')' => {
+                pos += 1;
+                TokenKind::RightParen
+            }
+            '/' => TokenKind::Regex(read_delimited(&chars, &mut pos, '/')?),
+            '"' => TokenKind::Text(read_delimited(&chars, &mut pos, '"')?),
+            _ => {
+                while pos < chars.len()
+                    && !chars[pos].is_whitespace()
+                    && !"()\"".contains(chars[pos])
+                {
+                    pos += 1;
+                }
+                let word: String = chars[start..pos].iter().collect();
+                match word.as_str() {
+                    "AND" => TokenKind::And,
+                    "OR" => TokenKind::Or,
+                    "NOT" => TokenKind::Not,
+                    _ => read_field(&word, &chars, &mut pos)?.unwrap_or(TokenKind::Text(word)),
+                }
+            }
+        };
+        tokens.push(Token {
+            kind,
+            position: start,
+        });
+    }
+    Ok(tokens)
+}
+
+/// Reads text enclosed in `delimiter`, starting at the opening one. Delimiter is escaped with backslash.
+fn read_delimited(chars: &[char], pos: &mut usize, delimiter: char) -> Result<String, QueryError> {
+    let start = *pos;
+    let mut text = String::new();
+    *pos += 1;
+    loop {
+        match chars.get(*pos) {
+            None => return Err(error(start, format!("missing closing {delimiter}"))),
+            Some('\\') if chars.get(*pos + 1) == Some(&delimiter) => {
+                text.push(delimiter);
+                *pos += 2;
+            }
+            Some(c) if *c == delimiter => {
+                *pos += 1;
+                return Ok(text);
+            }
+            Some(c) => {
+                text.push(*c);
+                *pos += 1;
+            }
+        }
+    }
+}
+
+/// Recognizes `word` as field term. Quoted value (`time>"2023-03-14 10:00"`) is read from `chars`.
+fn read_field(
+    word: &str,
+    chars: &[char],
+    pos: &mut usize,
+) -> Result<Option<TokenKind>, QueryError> {
+    let name_len = word
+        .find(|c: char| !(c.is_alphanumeric() || "_.-".contains(c)))
+        .unwrap_or(word.len());
+    let (name, rest) = word.split_at(name_len);
+    let Some(operator) = OPERATORS.iter().find(|op| rest.starts_with(*op)) else {
+        return Ok(None);
+    };
+    // Parsed fields are only compared, so words like `http://host` stay text
+    if name.is_empty() || (*operator == ":" && !FIELDS.contains(&name)) {
+        return Ok(None);
+    }
+    let mut value = String::from(&rest[operator.len()..]);
+    if value.is_empty() && chars.get(*pos) == Some(&'"') {
+        value = read_delimited(chars, pos, '"')?;
+    }
+    Ok(Some(TokenKind::Field {
+        name: String::from(name),
+        operator: String::from(*operator),
+        value,
+    }))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Query length, position reported when query ends unexpectedly
+    end: usize,
+    current: Option<Timestamp>,
+    /// Patterns of text terms that are not negated
+    highlights: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self, negated: bool) -> Result<Filter, QueryError> {
+        let mut alternatives = vec![self.parse_and(negated)?];
+        while self.peek().map(|t| &t.kind) == Some(&TokenKind::Or) {
+            self.next();
+            alternatives.push(self.parse_and(negated)?);
+        }
+        Ok(group_if_many(alternatives, Filter::Any))
+    }
+
+    fn parse_and(&mut self, negated: bool) -> Result<Filter, QueryError> {
+        let mut terms = vec![self.parse_unary(negated)?];
+        loop {
+            match self.peek().map(|t| &t.kind) {
+                None | Some(TokenKind::Or | TokenKind::RightParen) => break,
+                Some(TokenKind::And) => {
+                    self.next();
+                }
+                Some(_) => {}
+            }
+            terms.push(self.parse_unary(negated)?);
+        }
+        Ok(group_if_many(terms, Filter::All))
+    }
+
+    fn parse_unary(&mut self, negated: bool) -> Result<Filter, QueryError> {
+        let Some(token) = self.next() else {
+            return Err(error(self.end, "unexpected end of query"));
+        };
+        match token.kind {
+            TokenKind::Not => Ok(Filter::Not(Box::new(self.parse_unary(!negated)?))),
+            TokenKind::LeftParen => {
+                let inner = self.parse_or(negated)?;
+                match self.next() {
+                    Some(Token {
+                        kind: TokenKind::RightParen,
+                        ..
+                    }) => Ok(inner),
+                    Some(t) => Err(error(t.position, "expected )")),
+                    None => Err(error(self.end, "missing closing )")),
+                }
+            }
+            TokenKind::Regex(pattern) => self.text_filter(&pattern, token.position, negated),
+            TokenKind::Text(text) => {
+                self.text_filter(&regex::escape(&text), token.position, negated)
+            }
+            TokenKind::Field {
+                name,
+                operator,
+                value,
+            } => self.field_filter(&name, &operator, &value, token.position),
+            TokenKind::And | TokenKind::Or | TokenKind::RightParen => {
+                Err(error(token.position, "expected a term"))
+            }
+        }
+    }
+
+    fn text_filter(
+        &mut self,
+        pattern: &str,
+        position: usize,
+        negated: bool,
+    ) -> Result<Filter, QueryError> {
+        let regex =
+            Regex::new(pattern).map_err(|e| error(position, format!("invalid regex: {e}")))?;
+        if !negated {
+            self.highlights.push(pattern.to_string());
+        }
+        Ok(Filter::Regex(RegexFilter::from(regex)))
+    }
+
+    fn field_filter(
+        &self,
+        name: &str,
+        operator: &str,
+        value: &str,
+        position: usize,
+    ) -> Result<Filter, QueryError> {
+        match (name, operator) {
+            ("level", ":" | ">=") => {
+                let value_position = position + name.len() + operator.len();
+                let level = value
+                    .parse::<Level>()
+                    .map_err(|e| error(value_position, e))?;
+                match operator {
+                    ":" => Ok(Filter::Level(level)),
+                    _ => Ok(Filter::MinLevel(level)),
+                }
+            }
+            ("time", ":") => Err(error(position, "time needs one of >, >=, <, <=")),
+            ("time", _) => {
+                let value_position = position + name.len() + operator.len();
+                let time = value
+                    .parse::<TimeSpec>()
+                    .map_err(|e| error(value_position, e))?
+                    .resolve(self.current)
+                    .ok_or_else(|| error(value_position, "current line has no timestamp"))?;
+                let after = time + Duration::nanoseconds(1);
+                let before = time - Duration::nanoseconds(1);
+                let (from, to) = match operator {
+                    ">" => (Some(after), None),
+                    ">=" => (Some(time), None),
+                    "<" => (None, Some(before)),
+                    _ => (None, Some(time)),
+                };
+                Ok(Filter::Time(TimeFilter {
+                    from,
+                    to,
+                    negate: false,
+                }))
+            }
+            (name, _) if !FIELDS.contains(&name) => {
+                let value_position = position + name.chars().count() + operator.len();
+                format!("{name} {operator} {value}")
+                    .parse::<FieldFilter>()
+                    .map(Filter::Field)
+                    .map_err(|e| error(value_position, e))
+            }
+            _ => Err(error(position, format!("invalid {name} term"))),
+        }
+    }
+}
+
+/// Avoids wrapping single filter in a group
+fn group_if_many(mut filters: Vec<Filter>, group: fn(Vec<Filter>) -> Filter) -> Filter {
+    if filters.len() == 1 {
+        filters.remove(0)
+    } else {
+        group(filters)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FilterInput;
+
+    fn matches(query: &str, line: &str) -> bool {
+        Query::parse(query, None)
+            .unwrap()
+            .filter
+            .is_match(&FilterInput {
+                level: Level::detect(line),
+                ..FilterInput::text(line)
+            })
+    }
+
+    #[test]
+    fn evaluates_boolean_expressions() {
+        let query = "level:error AND NOT /health.*/";
+        assert!(matches(query, "ERROR db down"));
+        assert!(!matches(query, "ERROR healthcheck failed"));
+        assert!(!matches(query, "INFO errors: 0"));
+
+        let query = "(ERROR OR WARN) -v2 \"a b\"";
+        assert!(matches(query, "WARN -v2 a b"));
+        assert!(!matches(query, "WARN -v2 ab"));
+        assert!(!matches(query, "INFO -v2 a b"));
+
+        assert!(matches("a OR b c", "a"));
+        assert!(!matches("a OR b c", "b"));
+        assert!(matches(r"/a\/b/", "a/b"));
+    }
+
+    #[test]
+    fn filters_by_level() {
+        assert!(matches("level:warn", "WARNING disk almost full"));
+        assert!(!matches("level:error", "INFO retry after error"));
+        assert!(!matches("level:error", "no level"));
+        assert!(matches("level>=warn", "ERROR db down"));
+        assert!(!matches("level>=warn", "INFO started"));
+        assert!(Query::parse("level:loud", None).is_err());
+        assert!(Query::parse("level<warn", None).is_err());
+    }
+
+    #[test]
+    fn filters_by_fields() {
+        let fields = [("status", "503")]
+            .into_iter()
+            .map(|(k, v)| (String::from(k), String::from(v)))
+            .collect();
+        let is_match = |query: &str| {
+            Query::parse(query, None)
+                .unwrap()
+                .filter
+                .is_match(&FilterInput {
+                    fields: Some(&fields),
+                    ..FilterInput::text("GET /")
+                })
+        };
+        assert!(is_match("status>=500"));
+        assert!(is_match("GET status>500"));
+        assert!(!is_match("status<500"));
+        assert!(!is_match("missing>1"));
+        assert!(is_match("http://host OR status>=500"));
+    }
+
+    #[test]
+    fn filters_by_time() {
+        let current = "2023-03-14 10:00:00"
+            .parse::<TimeSpec>()
+            .unwrap()
+            .resolve(None);
+        let query = Query::parse("time>=09:00 time<+1h", current).unwrap();
+        let at = |time: &str| {
+            format!("2023-03-14 {time}")
+                .parse::<TimeSpec>()
+                .unwrap()
+                .resolve(None)
+        };
+
+        let is_match = |timestamp| {
+            query.filter.is_match(&FilterInput {
+                timestamp,
+                ..Default::default()
+            })
+        };
+        assert!(is_match(at("09:00:00")));
+        assert!(is_match(at("10:59:59")));
+        assert!(!is_match(at("11:00:00")));
+        assert!(!is_match(at("08:59:59")));
+        assert!(!is_match(None));
+    }
+
+    #[test]
+    fn highlights_positive_terms() {
+        let query = Query::parse("ERROR NOT health", None).unwrap();
+        assert_eq!(query.highlight.unwrap().as_str(), "(?:ERROR)");
+
+        let highlight = Query::parse("/(?i)error/ foo", None)
+            .unwrap()
+            .highlight
+            .unwrap();
+        assert!(highlight.is_match("Error"));
+        assert!(!highlight.is_match("FOO"));
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        let position = |query: &str| Query::parse(query, None).unwrap_err().position;
+        assert_eq!(position("a AND"), 5);
+        assert_eq!(position("a (b"), 4);
+        assert_eq!(position("a /b"), 2);
+        assert_eq!(position("a b)"), 3);
+        assert_eq!(position("x time>soon"), 7);
+        assert_eq!(position("time>10:00"), 5);
+        assert_eq!(position("a status>=many"), 10);
+        assert_eq!(position("OR a"), 0);
+        assert_eq!(position("é /(/"), 2);
+    }
+}
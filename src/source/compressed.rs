@@ -0,0 +1,109 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Compression formats recognized by their magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    const MAGIC: [(&'static [u8], Compression); 3] = [
+        (&[0x1f, 0x8b], Compression::Gzip),
+        (&[0x28, 0xb5, 0x2f, 0xfd], Compression::Zstd),
+        (&[0xfd, b'7', b'z', b'X', b'Z', 0x00], Compression::Xz),
+    ];
+
+    pub fn detect(header: &[u8]) -> Option<Compression> {
+        Self::MAGIC
+            .iter()
+            .find(|(magic, _)| header.starts_with(magic))
+            .map(|(_, compression)| *compression)
+    }
+
+    /// Detects compression of a file by looking at its first bytes. Extension is not taken into account.
+    pub fn detect_file<P: AsRef<Path>>(path: P) -> std::io::Result<Option<Compression>> {
+        let mut header = Vec::with_capacity(8);
+        File::open(path)?.take(8).read_to_end(&mut header)?;
+        Ok(Compression::detect(&header))
+    }
+
+    /// Wraps compressed stream into streaming decoder
+    pub fn decoder<'a, R: Read + 'a>(&self, reader: R) -> std::io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            // Multi member decoder handles concatenated archives (e.g. produced by `cat a.gz b.gz`)
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+            Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Xz => "xz",
+        }
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+    use crate::source::{LineSource, MemorySource};
+
+    const TEXT: &str = "line1\nline2\n";
+
+    fn decompress_lines(compressed: &[u8]) -> Vec<String> {
+        let compression = Compression::detect(compressed).unwrap();
+        let source =
+            MemorySource::from_reader(compression.decoder(compressed).unwrap(), None).unwrap();
+        (0..source.line_count())
+            .map(|n| source.line(n).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn reads_gzip() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(TEXT.as_bytes()).unwrap();
+        assert_eq!(
+            decompress_lines(&encoder.finish().unwrap()),
+            vec!["line1", "line2"]
+        );
+    }
+
+    #[test]
+    fn reads_zstd() {
+        let compressed = zstd::encode_all(TEXT.as_bytes(), 0).unwrap();
+        assert_eq!(decompress_lines(&compressed), vec!["line1", "line2"]);
+    }
+
+    #[test]
+    fn reads_xz() {
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 1);
+        encoder.write_all(TEXT.as_bytes()).unwrap();
+        assert_eq!(
+            decompress_lines(&encoder.finish().unwrap()),
+            vec!["line1", "line2"]
+        );
+    }
+
+    #[test]
+    fn plain_text_is_not_compressed() {
+        assert_eq!(Compression::detect(TEXT.as_bytes()), None);
+        assert_eq!(Compression::detect(&[]), None);
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, Read};
+use std::str::FromStr;
+
+/// Text encodings supported by line sources. Undecodable input is always replaced, never rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Latin1,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    const BOM: [(&'static [u8], Encoding); 3] = [
+        (&[0xef, 0xbb, 0xbf], Encoding::Utf8),
+        (&[0xff, 0xfe], Encoding::Utf16Le),
+        (&[0xfe, 0xff], Encoding::Utf16Be),
+    ];
+
+    /// Detects encoding from byte order mark. Returns the encoding and BOM length.
+    pub fn from_bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+        Self::BOM
+            .iter()
+            .find(|(bom, _)| bytes.starts_with(bom))
+            .map(|(bom, encoding)| (*encoding, bom.len()))
+    }
+
+    /// Decodes single line. Returned flag tells if any bytes had to be replaced.
+    pub fn decode(&self, bytes: &[u8]) -> (String, bool) {
+        match self {
+            Encoding::Utf8 => match String::from_utf8_lossy(bytes) {
+                std::borrow::Cow::Borrowed(s) => (s.to_string(), false),
+                std::borrow::Cow::Owned(s) => (s, true),
+            },
+            Encoding::Latin1 => (bytes.iter().map(|&b| b as char).collect(), false),
+            Encoding::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+            Encoding::Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+        }
+    }
+
+    /// Reads bytes of a single line, including the terminating newline, into `buf`.
+    /// Returns number of bytes read, zero meaning end of stream.
+    pub(crate) fn read_line<R: BufRead>(
+        &self,
+        reader: &mut R,
+        buf: &mut Vec<u8>,
+    ) -> std::io::Result<usize> {
+        let newline = match self {
+            Encoding::Utf8 | Encoding::Latin1 => return reader.read_until(b'\n', buf),
+            Encoding::Utf16Le => [b'\n', 0],
+            Encoding::Utf16Be => [0, b'\n'],
+        };
+        let start = buf.len();
+        let mut unit = [0u8; 2];
+        loop {
+            match reader.read_exact(&mut unit) {
+                Ok(()) => buf.extend_from_slice(&unit),
+                // Odd trailing byte cannot be decoded anyway
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+            if unit == newline {
+                break;
+            }
+        }
+        Ok(buf.len() - start)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Latin1 => "latin1",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+        }
+    }
+}
+
+/// Splits a byte stream into decoded lines
+pub(crate) struct LineReader<R> {
+    reader: BufReader<R>,
+    encoding: Encoding,
+    buf: Vec<u8>,
+}
+
+impl<R: Read> LineReader<R> {
+    /// Without explicit `encoding` the stream is decoded as UTF-8 unless it starts with a byte order mark.
+    pub fn new(reader: R, encoding: Option<Encoding>) -> std::io::Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let bom = Encoding::from_bom(reader.fill_buf()?);
+        let encoding = match (encoding, bom) {
+            (Some(encoding), _) => encoding,
+            (None, Some((detected, _))) => detected,
+            (None, None) => Encoding::Utf8,
+        };
+        if let Some((_, bom_len)) = bom.filter(|(detected, _)| *detected == encoding) {
+            reader.consume(bom_len);
+        }
+        Ok(LineReader {
+            reader,
+            encoding,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Returns next line without the newline and a flag telling if any bytes had to be replaced
+    pub fn next_line(&mut self) -> std::io::Result<Option<(String, bool)>> {
+        self.buf.clear();
+        if self.encoding.read_line(&mut self.reader, &mut self.buf)? == 0 {
+            return Ok(None);
+        }
+        let (mut line, lossy) = self.encoding.decode(&self.buf);
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some((line, lossy)))
+    }
+
+    /// Tells if next line can be read without waiting for the underlying reader
+    pub fn has_buffered_data(&self) -> bool {
+        !self.reader.buffer().is_empty()
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> (String, bool) {
+    let mut lossy = !bytes.len().is_multiple_of(2);
+    let units = bytes.chunks_exact(2).map(|c| from_bytes([c[0], c[1]]));
+    let text = char::decode_utf16(units)
+        .map(|c| {
+            c.unwrap_or_else(|_| {
+                lossy = true;
+                char::REPLACEMENT_CHARACTER
+            })
+        })
+        .collect();
+    (text, lossy)
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "latin1" | "latin-1" | "iso-8859-1" => Ok(Encoding::Latin1),
+            "utf-16le" | "utf16le" => Ok(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Encoding::Utf16Be),
+            _ => Err(format!(
+                "unsupported encoding: {s} (expected one of utf-8, latin1, utf-16le, utf-16be)"
+            )),
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decodes_latin1() {
+        assert_eq!(
+            Encoding::Latin1.decode(b"caf\xe9"),
+            (String::from("café"), false)
+        );
+    }
+
+    #[test]
+    fn marks_invalid_utf8_as_lossy() {
+        assert_eq!(Encoding::Utf8.decode(b"ok"), (String::from("ok"), false));
+        assert_eq!(
+            Encoding::Utf8.decode(b"bad\xff"),
+            (String::from("bad\u{fffd}"), true)
+        );
+    }
+
+    #[test]
+    fn reads_utf16_lines() {
+        let bytes: Vec<u8> = "a\nb\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let mut reader = bytes.as_slice();
+        let mut buf = Vec::new();
+        assert_eq!(
+            Encoding::Utf16Le.read_line(&mut reader, &mut buf).unwrap(),
+            4
+        );
+        assert_eq!(Encoding::Utf16Le.decode(&buf), (String::from("a\n"), false));
+    }
+
+    #[test]
+    fn detects_bom() {
+        assert_eq!(
+            Encoding::from_bom(&[0xff, 0xfe, b'a', 0]),
+            Some((Encoding::Utf16Le, 2))
+        );
+        assert_eq!(Encoding::from_bom(b"plain"), None);
+    }
+}
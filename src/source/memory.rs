@@ -0,0 +1,104 @@
+use std::collections::BTreeSet;
+use std::io::Read;
+
+use super::encoding::LineReader;
+use super::{Encoding, LineSource};
+
+/// Keeps every line as a separate owned string. Suitable for small logs and text that is already in memory.
+#[derive(Debug, Default, Clone)]
+pub struct MemorySource {
+    lines: Vec<String>,
+    lossy: BTreeSet<usize>,
+}
+
+impl MemorySource {
+    pub fn new(text: &str) -> Self {
+        MemorySource {
+            lines: text.lines().map(String::from).collect(),
+            lossy: BTreeSet::new(),
+        }
+    }
+
+    /// Reads and decodes the whole stream line by line. Invalid input is replaced, not rejected.
+    ///
+    /// Without explicit `encoding` the stream is decoded as UTF-8 unless it starts with a byte order mark.
+    pub fn from_reader<R: Read>(reader: R, encoding: Option<Encoding>) -> std::io::Result<Self> {
+        let mut reader = LineReader::new(reader, encoding)?;
+        let mut source = MemorySource::default();
+        while let Some((line, lossy)) = reader.next_line()? {
+            if lossy {
+                source.lossy.insert(source.lines.len());
+            }
+            source.lines.push(line);
+        }
+        Ok(source)
+    }
+}
+
+impl From<Vec<String>> for MemorySource {
+    fn from(lines: Vec<String>) -> Self {
+        MemorySource {
+            lines,
+            lossy: BTreeSet::new(),
+        }
+    }
+}
+
+impl LineSource for MemorySource {
+    fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn line(&self, n: usize) -> Option<&str> {
+        self.lines.get(n).map(String::as_str)
+    }
+
+    fn is_lossy(&self, n: usize) -> bool {
+        self.lossy.contains(&n)
+    }
+
+    fn push_line(&mut self, line: String, lossy: bool) {
+        if lossy {
+            self.lossy.insert(self.lines.len());
+        }
+        self.lines.push(line)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn all_lines(source: &MemorySource) -> Vec<&str> {
+        (0..source.line_count())
+            .map(|n| source.line(n).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn reads_utf16_with_bom() {
+        let mut bytes = vec![0xfe, 0xff];
+        bytes.extend(
+            "first\r\nsecond\n"
+                .encode_utf16()
+                .flat_map(u16::to_be_bytes),
+        );
+        let source = MemorySource::from_reader(bytes.as_slice(), None).unwrap();
+        assert_eq!(all_lines(&source), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn marks_lossy_lines() {
+        let source = MemorySource::from_reader(&b"ok\nbad\xff\n"[..], None).unwrap();
+        assert_eq!(all_lines(&source), vec!["ok", "bad\u{fffd}"]);
+        assert!(!source.is_lossy(0));
+        assert!(source.is_lossy(1));
+    }
+
+    #[test]
+    fn explicit_encoding_wins_over_default() {
+        let source = MemorySource::from_reader(&b"caf\xe9\n"[..], Some(Encoding::Latin1)).unwrap();
+        assert_eq!(all_lines(&source), vec!["café"]);
+        assert!(!source.is_lossy(0));
+    }
+}
@@ -0,0 +1,182 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use super::{LineSource, MemorySource};
+use crate::timestamp::{Timestamp, TimestampDetector};
+
+/// Interleaves several sources into a single timeline ordered by line timestamps.
+///
+/// Timestamp format is detected separately for every source. Lines without a timestamp (e.g. stack traces) stay
+/// attached to the closest preceding line of the same source. Lines of each source keep their relative order even if
+/// timestamps in the source are not monotonic. Lines with equal timestamps are taken from the source given first.
+pub struct MergedSource {
+    sources: Vec<Box<dyn LineSource>>,
+    /// Source index and line number in that source for every merged line
+    order: Vec<(u16, usize)>,
+    appended: MemorySource,
+    detectors: Vec<TimestampDetector>,
+}
+
+/// Run of lines starting with a timestamped line, ordered by (timestamp, source index)
+type Record = Reverse<(Option<Timestamp>, u16, usize)>;
+
+impl MergedSource {
+    pub fn new(sources: Vec<Box<dyn LineSource>>) -> Self {
+        assert!(sources.len() <= u16::MAX as usize, "Too many sources");
+        let detectors: Vec<_> = sources
+            .iter()
+            .map(|source| {
+                let mut detector = TimestampDetector::new();
+                detector.sample_lines((0..source.line_count()).filter_map(|n| source.line(n)));
+                detector
+            })
+            .collect();
+        let formats: Vec<_> = detectors.iter().map(TimestampDetector::format).collect();
+        let timestamp = |i: u16, n: usize| {
+            let format = formats[i as usize].as_ref()?;
+            sources[i as usize]
+                .line(n)
+                .and_then(|line| format.parse(line))
+        };
+
+        let mut heap: BinaryHeap<Record> = sources
+            .iter()
+            .enumerate()
+            .filter(|(_, source)| source.line_count() > 0)
+            .map(|(i, _)| Reverse((timestamp(i as u16, 0), i as u16, 0)))
+            .collect();
+
+        let mut order = Vec::with_capacity(sources.iter().map(|s| s.line_count()).sum());
+        while let Some(Reverse((_, i, start))) = heap.pop() {
+            let source = sources[i as usize].as_ref();
+            let mut n = start;
+            loop {
+                order.push((i, n));
+                n += 1;
+                if n == source.line_count() {
+                    break;
+                }
+                if let Some(ts) = timestamp(i, n) {
+                    heap.push(Reverse((Some(ts), i, n)));
+                    break;
+                }
+            }
+        }
+
+        MergedSource {
+            sources,
+            order,
+            appended: MemorySource::default(),
+            detectors,
+        }
+    }
+
+    /// Index of the source line `n` was taken from. Appended lines have no source.
+    pub fn source_of(&self, n: usize) -> Option<u16> {
+        self.order.get(n).map(|(i, _)| *i)
+    }
+
+    /// Timestamp format detection of every source
+    pub(crate) fn timestamp_detectors(&self) -> &[TimestampDetector] {
+        &self.detectors
+    }
+}
+
+impl LineSource for MergedSource {
+    fn line_count(&self) -> usize {
+        self.order.len() + self.appended.line_count()
+    }
+
+    fn line(&self, n: usize) -> Option<&str> {
+        match self.order.get(n) {
+            Some((i, line)) => self.sources[*i as usize].line(*line),
+            None => self.appended.line(n - self.order.len()),
+        }
+    }
+
+    fn is_lossy(&self, n: usize) -> bool {
+        match self.order.get(n) {
+            Some((i, line)) => self.sources[*i as usize].is_lossy(*line),
+            None => self.appended.is_lossy(n - self.order.len()),
+        }
+    }
+
+    fn push_line(&mut self, line: String, lossy: bool) {
+        self.appended.push_line(line, lossy)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MemorySource;
+
+    fn merge(logs: &[&str]) -> MergedSource {
+        MergedSource::new(
+            logs.iter()
+                .map(|log| Box::new(MemorySource::new(log)) as Box<dyn LineSource>)
+                .collect(),
+        )
+    }
+
+    fn lines(source: &MergedSource) -> Vec<&str> {
+        (0..source.line_count())
+            .map(|n| source.line(n).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn interleaves_by_timestamp() {
+        let merged = merge(&[
+            "2023-01-01 10:00:00 a1\n2023-01-01 10:00:02 a2\n",
+            "2023-01-01 10:00:01 b1\n2023-01-01 10:00:03 b2\n",
+        ]);
+        assert_eq!(
+            lines(&merged),
+            vec![
+                "2023-01-01 10:00:00 a1",
+                "2023-01-01 10:00:01 b1",
+                "2023-01-01 10:00:02 a2",
+                "2023-01-01 10:00:03 b2",
+            ]
+        );
+        assert_eq!(merged.source_of(1), Some(1));
+        assert_eq!(merged.source_of(4), None);
+    }
+
+    #[test]
+    fn keeps_continuation_lines_with_their_record() {
+        let merged = merge(&[
+            "2023-01-01 10:00:00 a1\n  trace1\n  trace2\n2023-01-01 10:00:02 a2\n",
+            "2023-01-01 10:00:00 b1\n2023-01-01 10:00:01 b2\n",
+        ]);
+        assert_eq!(
+            lines(&merged),
+            vec![
+                "2023-01-01 10:00:00 a1",
+                "  trace1",
+                "  trace2",
+                "2023-01-01 10:00:00 b1",
+                "2023-01-01 10:00:01 b2",
+                "2023-01-01 10:00:02 a2",
+            ]
+        );
+    }
+
+    #[test]
+    fn puts_lines_before_first_timestamp_first() {
+        let merged = merge(&[
+            "2023-01-01 10:00:00 a1\n",
+            "header\n2023-01-01 10:00:01 b1\n2023-01-01 10:00:02 b2\n",
+        ]);
+        assert_eq!(
+            lines(&merged),
+            vec![
+                "header",
+                "2023-01-01 10:00:00 a1",
+                "2023-01-01 10:00:01 b1",
+                "2023-01-01 10:00:02 b2"
+            ]
+        );
+    }
+}
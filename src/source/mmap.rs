@@ -0,0 +1,192 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::path::Path;
+
+use log::debug;
+use memmap2::Mmap;
+
+use super::LineSource;
+
+/// Memory maps a log file and keeps only an index of line start offsets.
+///
+/// Line content is sliced directly from the mapping when requested, so the file is never copied into the heap.
+/// Lines that are not valid UTF-8 are the only exception - they are decoded lossily once, while building the
+/// index.
+///
+/// The mapping is never extended. Lines pushed after opening are kept in memory.
+pub struct MmapSource {
+    map: Option<Mmap>,
+    starts: Vec<usize>,
+    decoded: HashMap<usize, String>,
+    appended: Vec<String>,
+    /// Indexes of appended lines with replaced undecodable bytes
+    appended_lossy: BTreeSet<usize>,
+}
+
+impl MmapSource {
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+        // Mapping an empty file is not portable
+        let map = if file.metadata()?.len() == 0 {
+            None
+        } else {
+            // SAFETY: The mapping is read only. Sherlog cannot prevent other processes from modifying the file,
+            // which is the same limitation every mmap based viewer has. Reading lines of a file truncated after
+            // mapping faults, so files expected to change (followed ones) must not be opened this way.
+            Some(unsafe { Mmap::map(&file)? })
+        };
+        Ok(MmapSource::from_map(map))
+    }
+
+    fn from_map(map: Option<Mmap>) -> Self {
+        let bytes = map.as_deref().unwrap_or_default();
+        let starts = index_line_starts(bytes);
+        let mut source = MmapSource {
+            map,
+            starts,
+            decoded: HashMap::new(),
+            appended: Vec::new(),
+            appended_lossy: BTreeSet::new(),
+        };
+        source.decode_invalid_lines();
+        debug!("MmapSource indexed {} lines", source.starts.len());
+        source
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.map.as_deref().unwrap_or_default()
+    }
+
+    fn line_bytes(&self, n: usize) -> Option<&[u8]> {
+        let start = *self.starts.get(n)?;
+        let end = self
+            .starts
+            .get(n + 1)
+            .copied()
+            .unwrap_or(self.bytes().len());
+        let line = &self.bytes()[start..end];
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        Some(line.strip_suffix(b"\r").unwrap_or(line))
+    }
+
+    fn decode_invalid_lines(&mut self) {
+        // Validating the whole mapping at once is much faster than going line by line
+        if std::str::from_utf8(self.bytes()).is_ok() {
+            return;
+        }
+        let decoded: HashMap<_, _> = (0..self.starts.len())
+            .filter_map(|n| {
+                let line = self.line_bytes(n)?;
+                match std::str::from_utf8(line) {
+                    Ok(_) => None,
+                    Err(_) => Some((n, String::from_utf8_lossy(line).into_owned())),
+                }
+            })
+            .collect();
+        self.decoded = decoded;
+    }
+}
+
+impl LineSource for MmapSource {
+    fn line_count(&self) -> usize {
+        self.starts.len() + self.appended.len()
+    }
+
+    fn line(&self, n: usize) -> Option<&str> {
+        if n >= self.starts.len() {
+            return self.appended.get(n - self.starts.len()).map(String::as_str);
+        }
+        if let Some(decoded) = self.decoded.get(&n) {
+            return Some(decoded);
+        }
+        // Lines with invalid content were decoded while indexing
+        self.line_bytes(n).and_then(|l| std::str::from_utf8(l).ok())
+    }
+
+    fn is_lossy(&self, n: usize) -> bool {
+        match n.checked_sub(self.starts.len()) {
+            Some(appended) => self.appended_lossy.contains(&appended),
+            None => self.decoded.contains_key(&n),
+        }
+    }
+
+    fn push_line(&mut self, line: String, lossy: bool) {
+        if lossy {
+            self.appended_lossy.insert(self.appended.len());
+        }
+        self.appended.push(line)
+    }
+}
+
+/// Returns offsets of all line beginnings, following `str::lines` semantics (no empty line after trailing newline)
+fn index_line_starts(bytes: &[u8]) -> Vec<usize> {
+    const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+    let first = if bytes.starts_with(UTF8_BOM) {
+        UTF8_BOM.len()
+    } else {
+        0
+    };
+    if bytes.len() <= first {
+        return Vec::new();
+    }
+    let mut starts = vec![first];
+    starts.extend(
+        memchr::memchr_iter(b'\n', bytes)
+            .map(|pos| pos + 1)
+            .filter(|&start| start < bytes.len()),
+    );
+    starts
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+
+    fn open_with(content: &[u8]) -> MmapSource {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(content).unwrap();
+        MmapSource::open(file.path()).unwrap()
+    }
+
+    fn all_lines(source: &MmapSource) -> Vec<&str> {
+        (0..source.line_count())
+            .map(|n| source.line(n).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn splits_lines_like_str_lines() {
+        let text = "line1\r\nline2\n\nline4\n";
+        let source = open_with(text.as_bytes());
+        assert_eq!(all_lines(&source), text.lines().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn handles_missing_trailing_newline() {
+        let source = open_with(b"line1\nline2");
+        assert_eq!(all_lines(&source), vec!["line1", "line2"]);
+    }
+
+    #[test]
+    fn handles_empty_file() {
+        let source = open_with(b"");
+        assert_eq!(source.line_count(), 0);
+        assert_eq!(source.line(0), None);
+    }
+
+    #[test]
+    fn decodes_invalid_lines_lossily() {
+        let source = open_with(b"ok\nbad\xff\nok again\n");
+        assert_eq!(all_lines(&source), vec!["ok", "bad\u{fffd}", "ok again"]);
+        assert!(source.is_lossy(1));
+        assert!(!source.is_lossy(2));
+    }
+
+    #[test]
+    fn skips_utf8_bom() {
+        let source = open_with(b"\xef\xbb\xbfline1\n");
+        assert_eq!(all_lines(&source), vec!["line1"]);
+    }
+}
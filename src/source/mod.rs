@@ -0,0 +1,79 @@
+mod compressed;
+mod encoding;
+mod memory;
+mod merged;
+mod mmap;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+pub use compressed::Compression;
+pub use encoding::Encoding;
+pub(crate) use encoding::LineReader;
+pub use memory::MemorySource;
+pub use merged::MergedSource;
+pub use mmap::MmapSource;
+
+/// Storage for the raw log lines processed by [`crate::Sherlog`].
+///
+/// Lines are addressed by their zero based position in the log. Implementations are expected to provide
+/// cheap random access since the core slices only the lines it is about to display.
+pub trait LineSource {
+    fn line_count(&self) -> usize;
+
+    fn line(&self, n: usize) -> Option<&str>;
+
+    /// Tells if line contained bytes that could not be decoded and were replaced
+    fn is_lossy(&self, _n: usize) -> bool {
+        false
+    }
+
+    /// Adds a line at the end. Used when the log grows while being viewed. `lossy` tells if undecodable bytes of the
+    /// line were replaced.
+    fn push_line(&mut self, line: String, lossy: bool);
+}
+
+impl<T: LineSource + ?Sized> LineSource for Box<T> {
+    fn line_count(&self) -> usize {
+        (**self).line_count()
+    }
+
+    fn line(&self, n: usize) -> Option<&str> {
+        (**self).line(n)
+    }
+
+    fn is_lossy(&self, n: usize) -> bool {
+        (**self).is_lossy(n)
+    }
+
+    fn push_line(&mut self, line: String, lossy: bool) {
+        (**self).push_line(line, lossy)
+    }
+}
+
+/// Opens log file with the most suitable source.
+///
+/// Plain UTF-8 files are memory mapped. Compressed files and files in other encodings (explicit or detected from
+/// byte order mark) are decoded into memory.
+pub fn open_file<P: AsRef<Path>>(
+    path: P,
+    encoding: Option<Encoding>,
+) -> std::io::Result<Box<dyn LineSource>> {
+    let path = path.as_ref();
+    if let Some(compression) = Compression::detect_file(path)? {
+        let decoder = compression.decoder(File::open(path)?)?;
+        return Ok(Box::new(MemorySource::from_reader(decoder, encoding)?));
+    }
+
+    let mut header = Vec::with_capacity(4);
+    File::open(path)?.take(4).read_to_end(&mut header)?;
+    let bom = Encoding::from_bom(&header).map(|(detected, _)| detected);
+    match encoding.or(bom) {
+        None | Some(Encoding::Utf8) => Ok(Box::new(MmapSource::open(path)?)),
+        encoding => Ok(Box::new(MemorySource::from_reader(
+            File::open(path)?,
+            encoding,
+        )?)),
+    }
+}
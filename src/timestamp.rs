@@ -0,0 +1,564 @@
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, NaiveTime, Utc};
+use regex::{Captures, Regex};
+
+use crate::parse::DETECT_SAMPLE_LINES;
+use crate::ty::text::line_prefix;
+use crate::TimeFilter;
+
+/// Normalized point in time of a log line.
+///
+/// Timestamps written without UTC offset are assumed to be in UTC, so logs without offsets can be compared with each
+/// other regardless of the machine sherlog runs on.
+pub type Timestamp = DateTime<Utc>;
+
+/// Length of the line start searched for a timestamp, see [`line_prefix`]
+const SEARCH_PREFIX_CHARS: usize = 64;
+
+/// Timestamp notations recognized in log lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimestampFormat {
+    /// `2023-03-14 08:15:02.125`, `2023-03-14T08:15:02Z` or RFC 3339 `2023-03-14T08:15:02+01:00`
+    Iso8601,
+    /// `Mar 14 08:15:02` at line start, optionally preceded by `<priority>`. Year of the reference time is assumed,
+    /// unless that puts the timestamp after it.
+    Syslog,
+    /// Apache Common Log Format `[14/Mar/2023:08:15:02 +0100]`
+    ApacheClf,
+    /// Unix time in seconds (`1678781702` or `1678781702.125`) at line start
+    EpochSeconds,
+    /// Unix time in milliseconds (`1678781702125`) at line start
+    EpochMillis,
+}
+
+impl TimestampFormat {
+    /// All formats in detection priority order
+    pub const ALL: [TimestampFormat; 5] = [
+        TimestampFormat::Iso8601,
+        TimestampFormat::Syslog,
+        TimestampFormat::ApacheClf,
+        TimestampFormat::EpochSeconds,
+        TimestampFormat::EpochMillis,
+    ];
+
+    /// Picks the format matching most of the first non-empty lines of a log, see [`TimestampDetector`]. Detection
+    /// time becomes the reference time of the log.
+    pub fn detect<'a, I: IntoIterator<Item = &'a str>>(
+        lines: I,
+    ) -> Option<DetectedTimestampFormat> {
+        let mut detector = TimestampDetector::new();
+        detector.sample_lines(lines);
+        detector.format()
+    }
+
+    /// Finds timestamp written in this format near the line start. `reference` is the time the log is read at, it
+    /// completes timestamps written without year.
+    pub fn parse(self, line: &str, reference: Timestamp) -> Option<Timestamp> {
+        self.find(line, reference).map(|(_, timestamp)| timestamp)
+    }
+
+    /// Finds timestamp like [`TimestampFormat::parse`], returns also where it is written in the line
+    pub fn find(self, line: &str, reference: Timestamp) -> Option<(Range<usize>, Timestamp)> {
+        let captures = self
+            .pattern()
+            .captures(line_prefix(line, SEARCH_PREFIX_CHARS))?;
+        let timestamp = match self {
+            TimestampFormat::Iso8601 => parse_iso(&captures),
+            TimestampFormat::Syslog => parse_syslog(&captures, reference),
+            TimestampFormat::ApacheClf => {
+                DateTime::parse_from_str(&captures[1], "%d/%b/%Y:%H:%M:%S %z")
+                    .ok()
+                    .map(|t| t.with_timezone(&Utc))
+            }
+            TimestampFormat::EpochSeconds => {
+                let secs = captures[1].parse().ok()?;
+                let nanos = match captures.get(2) {
+                    Some(fraction) => format!("{:0<9}", fraction.as_str()).parse().ok()?,
+                    None => 0,
+                };
+                DateTime::from_timestamp(secs, nanos)
+            }
+            TimestampFormat::EpochMillis => {
+                DateTime::from_timestamp_millis(captures[1].parse().ok()?)
+            }
+        }?;
+        Some((captures.get(0)?.range(), timestamp))
+    }
+
+    /// Where a timestamp of this format is written near the line start. Unlike [`TimestampFormat::find`] the
+    /// timestamp is not validated.
+    pub fn locate(self, line: &str) -> Option<Range<usize>> {
+        let found = self
+            .pattern()
+            .find(line_prefix(line, SEARCH_PREFIX_CHARS))?;
+        Some(found.range())
+    }
+
+    fn pattern(self) -> &'static Regex {
+        static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+        let patterns = PATTERNS.get_or_init(|| {
+            TimestampFormat::ALL
+                .iter()
+                .map(|format| Regex::new(format.regex()).unwrap())
+                .collect()
+        });
+        &patterns[self as usize]
+    }
+
+    fn regex(self) -> &'static str {
+        match self {
+            TimestampFormat::Iso8601 => {
+                r"(\d{4}-\d{2}-\d{2})[T ](\d{2}:\d{2}:\d{2})(?:[.,](\d{1,9}))?(Z|[+-]\d{2}:?\d{2})?"
+            }
+            TimestampFormat::Syslog => {
+                r"^(?:<\d{1,3}>)?([A-Z][a-z]{2}) {1,2}(\d{1,2}) (\d{2}:\d{2}:\d{2})\b"
+            }
+            TimestampFormat::ApacheClf => {
+                r"\[(\d{2}/[A-Z][a-z]{2}/\d{4}:\d{2}:\d{2}:\d{2} [+-]\d{4})\]"
+            }
+            TimestampFormat::EpochSeconds => r"^\[?(\d{10})(?:\.(\d{1,9}))?\b",
+            TimestampFormat::EpochMillis => r"^\[?(\d{13})\b",
+        }
+    }
+}
+
+/// Detects timestamp format from the first non-empty lines of a log, which may arrive over time
+#[derive(Debug, Clone)]
+pub(crate) struct TimestampDetector {
+    /// Lines with timestamp of every format in [`TimestampFormat::ALL`]
+    hits: [usize; TimestampFormat::ALL.len()],
+    sampled: usize,
+    /// All lines looked at, including those not sampled
+    seen: usize,
+    reference: Timestamp,
+}
+
+/// Lines looked at before detection stops waiting for enough sampled lines, so a log made of empty or indented lines
+/// is not read whole
+const DETECT_MAX_SEEN_LINES: usize = 100 * DETECT_SAMPLE_LINES;
+
+impl TimestampDetector {
+    pub fn new() -> Self {
+        TimestampDetector {
+            hits: Default::default(),
+            sampled: 0,
+            seen: 0,
+            reference: Utc::now(),
+        }
+    }
+
+    /// Tells if enough lines were sampled, further lines do not change the format
+    pub fn is_done(&self) -> bool {
+        self.sampled >= DETECT_SAMPLE_LINES || self.seen >= DETECT_MAX_SEEN_LINES
+    }
+
+    /// Counts timestamps found in the line. Indented lines without timestamp (e.g. stack traces) are not counted
+    /// at all, they say nothing about lines starting records.
+    pub fn sample(&mut self, line: &str) {
+        if self.is_done() {
+            return;
+        }
+        self.seen += 1;
+        if line.trim().is_empty() {
+            return;
+        }
+        let mut found = false;
+        for (i, format) in TimestampFormat::ALL.iter().enumerate() {
+            if format.parse(line, self.reference).is_some() {
+                self.hits[i] += 1;
+                found = true;
+            }
+        }
+        if found || !line.starts_with([' ', '\t']) {
+            self.sampled += 1;
+        }
+    }
+
+    /// Samples lines until enough of them are sampled
+    pub fn sample_lines<'a, I: IntoIterator<Item = &'a str>>(&mut self, lines: I) {
+        for line in lines {
+            if self.is_done() {
+                break;
+            }
+            self.sample(line);
+        }
+    }
+
+    /// Format found in more than half of sampled lines. A few stray timestamps (e.g. in command output) do not make
+    /// the log timestamped. On equal count the format listed first wins.
+    pub fn format(&self) -> Option<DetectedTimestampFormat> {
+        let (best, count) = self
+            .hits
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, count)| **count)?;
+        (*count * 2 > self.sampled).then_some(DetectedTimestampFormat {
+            format: TimestampFormat::ALL[best],
+            reference: self.reference,
+        })
+    }
+}
+
+/// Timestamp format detected in a log together with the time of detection, so lines without year are dated the
+/// same way every time they are parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectedTimestampFormat {
+    pub format: TimestampFormat,
+    pub reference: Timestamp,
+}
+
+impl DetectedTimestampFormat {
+    /// Finds timestamp near the line start, see [`TimestampFormat::parse`]
+    pub fn parse(&self, line: &str) -> Option<Timestamp> {
+        self.format.parse(line, self.reference)
+    }
+}
+
+fn parse_iso(captures: &Captures<'_>) -> Option<Timestamp> {
+    let fraction = captures.get(3).map_or("0", |m| m.as_str());
+    let local = format!("{}T{}.{}", &captures[1], &captures[2], fraction);
+    let naive = NaiveDateTime::parse_from_str(&local, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+    match captures.get(4).map(|m| m.as_str()) {
+        None | Some("Z") => Some(naive.and_utc()),
+        Some(offset) => {
+            let offset = offset.replace(':', "");
+            DateTime::parse_from_str(&format!("{local}{offset}"), "%Y-%m-%dT%H:%M:%S%.f%z")
+                .ok()
+                .map(|t| t.with_timezone(&Utc))
+        }
+    }
+}
+
+fn parse_syslog(captures: &Captures<'_>, now: Timestamp) -> Option<Timestamp> {
+    let parse = |year: i32| {
+        let text = format!("{year} {} {} {}", &captures[1], &captures[2], &captures[3]);
+        NaiveDateTime::parse_from_str(&text, "%Y %b %d %H:%M:%S")
+            .ok()
+            .map(|t| t.and_utc())
+    };
+    match parse(now.year()) {
+        // Log from the end of previous year
+        Some(t) if t > now + Duration::days(1) => parse(now.year() - 1),
+        // Feb 29 of a leap year viewed in the next year
+        None => parse(now.year() - 1),
+        t => t,
+    }
+}
+
+/// Point in time given by the user, e.g. as `goto` target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeSpec {
+    /// `2023-03-14 14:32:05` or `2023-03-14T14:32:05`
+    Absolute(Timestamp),
+    /// `14:32:05` or `14:32` on the day of the reference time
+    TimeOfDay(NaiveTime),
+    /// `+5m`, `-1h30m` from the reference time
+    Relative(Duration),
+}
+
+impl TimeSpec {
+    /// Turns into a timestamp. Only absolute time can be resolved without `reference`.
+    pub fn resolve(self, reference: Option<Timestamp>) -> Option<Timestamp> {
+        match self {
+            TimeSpec::Absolute(t) => Some(t),
+            TimeSpec::TimeOfDay(time) => Some(reference?.date_naive().and_time(time).and_utc()),
+            TimeSpec::Relative(delta) => reference?.checked_add_signed(delta),
+        }
+    }
+}
+
+impl FromStr for TimeSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(delta) = s.strip_prefix('+') {
+            return parse_duration(delta).map(TimeSpec::Relative);
+        }
+        if let Some(delta) = s.strip_prefix('-') {
+            return parse_duration(delta).map(|d| TimeSpec::Relative(-d));
+        }
+        for format in ["%H:%M:%S%.f", "%H:%M"] {
+            if let Ok(time) = NaiveTime::parse_from_str(s, format) {
+                return Ok(TimeSpec::TimeOfDay(time));
+            }
+        }
+        TimestampFormat::Iso8601
+            .parse(s, Utc::now())
+            .map(TimeSpec::Absolute)
+            .ok_or_else(|| {
+                format!("invalid time: {s} (expected e.g. 14:32:05, +5m or 2023-03-14 14:32:05)")
+            })
+    }
+}
+
+/// Time range given by the user, resolved into [`TimeFilter`] against the viewed log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeWindow {
+    /// `T1..T2`, `T1..` or `..T2`. Relative time and time of day refer to the current line.
+    Between(Option<TimeSpec>, Option<TimeSpec>),
+    /// `last 5m` of the log
+    Last(Duration),
+    /// `~5m` or `±5m` around the current line
+    Around(Duration),
+}
+
+impl TimeWindow {
+    /// Resolves bounds. `current` is the timestamp of the current line and `end` the last timestamp in the log.
+    /// Returns `None` when a needed timestamp is missing or a bound is out of the supported range.
+    pub fn resolve(self, current: Option<Timestamp>, end: Option<Timestamp>) -> Option<TimeFilter> {
+        let bound = |spec: Option<TimeSpec>| match spec {
+            Some(spec) => spec.resolve(current).map(Some),
+            None => Some(None),
+        };
+        let (from, to) = match self {
+            TimeWindow::Between(from, to) => (bound(from)?, bound(to)?),
+            TimeWindow::Last(delta) => (Some(end?.checked_sub_signed(delta)?), None),
+            TimeWindow::Around(delta) => (
+                Some(current?.checked_sub_signed(delta)?),
+                Some(current?.checked_add_signed(delta)?),
+            ),
+        };
+        Some(TimeFilter {
+            from,
+            to,
+            negate: false,
+        })
+    }
+}
+
+impl FromStr for TimeWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(delta) = s.strip_prefix("last ") {
+            return parse_duration(delta).map(TimeWindow::Last);
+        }
+        if let Some(delta) = s.strip_prefix('~').or_else(|| s.strip_prefix('±')) {
+            return parse_duration(delta).map(TimeWindow::Around);
+        }
+        let Some((from, to)) = s.split_once("..") else {
+            return Err(format!(
+                "invalid time window: {s} (expected T1..T2, last 5m or ~5m)"
+            ));
+        };
+        let parse = |t: &str| match t.trim() {
+            "" => Ok(None),
+            t => t.parse().map(Some),
+        };
+        Ok(TimeWindow::Between(parse(from)?, parse(to)?))
+    }
+}
+
+/// Parses duration like `30s`, `5m` or `1h30m`. Units: `ms`, `s`, `m`, `h`, `d`.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration: {s} (expected e.g. 30s, 5m or 1h30m)");
+    let mut total = Duration::zero();
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let value: i64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let units = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let part = match &rest[..units] {
+            "ms" => Duration::try_milliseconds(value),
+            "s" => Duration::try_seconds(value),
+            "m" => Duration::try_minutes(value),
+            "h" => Duration::try_hours(value),
+            "d" => Duration::try_days(value),
+            _ => None,
+        };
+        total = part
+            .and_then(|part| total.checked_add(&part))
+            .ok_or_else(invalid)?;
+        rest = &rest[units..];
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Reference time of parsed test logs
+    fn reference() -> Timestamp {
+        utc("2024-01-01 00:00:10").unwrap()
+    }
+
+    fn utc(text: &str) -> Option<Timestamp> {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+            .ok()
+            .map(|t| t.and_utc())
+    }
+
+    #[test]
+    fn parses_iso_timestamps() {
+        let expected = utc("2023-03-14 08:15:02.125");
+        let iso = TimestampFormat::Iso8601;
+        assert_eq!(
+            iso.parse("2023-03-14 08:15:02.125 INFO started", reference()),
+            expected
+        );
+        assert_eq!(
+            iso.parse("[2023-03-14T08:15:02,125] started", reference()),
+            expected
+        );
+        assert_eq!(
+            iso.parse("2023-03-14T08:15:02.125Z started", reference()),
+            expected
+        );
+        assert_eq!(
+            iso.parse("2023-03-14T09:15:02.125+01:00 x", reference()),
+            expected
+        );
+        assert_eq!(
+            iso.parse("2023-03-14T03:15:02.125-0500 x", reference()),
+            expected
+        );
+    }
+
+    #[test]
+    fn parses_other_formats() {
+        let expected = utc("2023-03-14 08:15:02");
+        assert_eq!(
+            TimestampFormat::ApacheClf.parse(
+                r#"127.0.0.1 - - [14/Mar/2023:09:15:02 +0100] "GET / HTTP/1.1" 200 2326"#,
+                reference()
+            ),
+            expected
+        );
+        assert_eq!(
+            TimestampFormat::EpochSeconds.parse("1678781702 started", reference()),
+            expected
+        );
+        assert_eq!(
+            TimestampFormat::EpochSeconds.parse("1678781702.5 started", reference()),
+            utc("2023-03-14 08:15:02.5")
+        );
+        assert_eq!(
+            TimestampFormat::EpochMillis.parse("1678781702000 started", reference()),
+            expected
+        );
+        assert_eq!(
+            TimestampFormat::EpochSeconds.parse("1678781702000 x", reference()),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_syslog_in_past_year() {
+        let captures = TimestampFormat::Syslog
+            .pattern()
+            .captures("<13>Dec 31 23:59:59 host app: bye")
+            .unwrap();
+        let now = reference();
+        assert_eq!(parse_syslog(&captures, now), utc("2023-12-31 23:59:59"));
+
+        let captures = TimestampFormat::Syslog
+            .pattern()
+            .captures("Mar  4 08:15:02 host app: hi")
+            .unwrap();
+        assert_eq!(parse_syslog(&captures, now), utc("2023-03-04 08:15:02"));
+    }
+
+    #[test]
+    fn ignores_lines_without_timestamp() {
+        for format in TimestampFormat::ALL {
+            assert_eq!(format.parse("    at com.example.Main", reference()), None);
+        }
+        assert_eq!(
+            TimestampFormat::Iso8601.parse("2023-13-45 99:00:00 not a date", reference()),
+            None
+        );
+    }
+
+    #[test]
+    fn detects_most_common_format() {
+        let log = [
+            "1678781702 boot",
+            "Mar 14 08:15:02 host app: started",
+            "Mar 14 08:15:03 host app: running",
+            "Mar 14 08:15:04 host app: stopped",
+            "  continuation",
+        ];
+        let detected = TimestampFormat::detect(log).unwrap();
+        assert_eq!(detected.format, TimestampFormat::Syslog);
+        assert!(detected
+            .parse(log[1])
+            .is_some_and(|t| t <= detected.reference + Duration::days(1)));
+        assert_eq!(TimestampFormat::detect(["no", "timestamps"]), None);
+    }
+
+    #[test]
+    fn ignores_stray_timestamps() {
+        let output = [
+            "running tests",
+            "",
+            "1678781702 seed",
+            "test a ... ok",
+            "test b ... FAIL",
+        ];
+        assert_eq!(TimestampFormat::detect(output), None);
+    }
+
+    #[test]
+    fn parses_time_specs() {
+        let reference = utc("2023-03-14 08:15:02");
+        let resolve = |s: &str| s.parse::<TimeSpec>().unwrap().resolve(reference);
+
+        assert_eq!(resolve("14:32:05"), utc("2023-03-14 14:32:05"));
+        assert_eq!(resolve("14:32"), utc("2023-03-14 14:32:00"));
+        assert_eq!(resolve("+5m"), utc("2023-03-14 08:20:02"));
+        assert_eq!(resolve("-1h30s"), utc("2023-03-14 07:14:32"));
+        assert_eq!(resolve("2023-03-15 00:00:00"), utc("2023-03-15 00:00:00"));
+        assert_eq!("+5m".parse::<TimeSpec>().unwrap().resolve(None), None);
+        assert!("+5x".parse::<TimeSpec>().is_err());
+        assert!("yesterday".parse::<TimeSpec>().is_err());
+        assert!(parse_duration("").is_err());
+    }
+
+    #[test]
+    fn resolves_time_windows() {
+        let current = utc("2023-03-14 08:15:00");
+        let end = utc("2023-03-14 09:00:00");
+        let resolve = |s: &str| {
+            s.parse::<TimeWindow>()
+                .unwrap()
+                .resolve(current, end)
+                .map(|f| (f.from, f.to))
+        };
+
+        assert_eq!(
+            resolve("08:00..08:30"),
+            Some((utc("2023-03-14 08:00:00"), utc("2023-03-14 08:30:00")))
+        );
+        assert_eq!(resolve("-5m.."), Some((utc("2023-03-14 08:10:00"), None)));
+        assert_eq!(
+            resolve("last 10m"),
+            Some((utc("2023-03-14 08:50:00"), None))
+        );
+        assert_eq!(
+            resolve("~1m"),
+            Some((utc("2023-03-14 08:14:00"), utc("2023-03-14 08:16:00")))
+        );
+        assert_eq!(
+            "~1m".parse::<TimeWindow>().unwrap().resolve(None, end),
+            None
+        );
+        assert_eq!(resolve("last 999999999d"), None);
+        assert_eq!(resolve("~999999999d"), None);
+        assert!("08:00".parse::<TimeWindow>().is_err());
+    }
+}
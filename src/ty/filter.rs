@@ -1,5 +1,126 @@
+use std::str::FromStr;
+
 use regex::Regex;
 
+use crate::{Fields, Level, Timestamp};
+
+/// Line data filters are evaluated against
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FilterInput<'a> {
+    pub text: &'a str,
+    /// Timestamp of the record line belongs to, see [`crate::Sherlog::record_timestamp`]
+    pub timestamp: Option<Timestamp>,
+    /// Fields extracted by the log parser
+    pub fields: Option<&'a Fields>,
+    /// Level of the record line belongs to, see [`crate::Sherlog::record_level`]
+    pub level: Option<Level>,
+}
+
+impl<'a> FilterInput<'a> {
+    pub fn text(text: &'a str) -> Self {
+        FilterInput {
+            text,
+            ..Default::default()
+        }
+    }
+}
+
+/// Condition a line has to meet to be shown. All filters passed to [`crate::Sherlog::filter`] have to match.
+#[derive(Clone, Debug)]
+pub enum Filter {
+    Regex(RegexFilter),
+    Time(TimeFilter),
+    Field(FieldFilter),
+    /// Level is at least as severe. Lines without level pass.
+    MinLevel(Level),
+    /// Level is exactly this one. Lines without level do not pass.
+    Level(Level),
+    /// Every filter matches. Empty group matches any line.
+    All(Vec<Filter>),
+    /// At least one filter matches. Empty group matches no line.
+    Any(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn is_match(&self, input: &FilterInput<'_>) -> bool {
+        match self {
+            Filter::Regex(filter) => filter.is_match(input.text),
+            Filter::Time(filter) => filter.is_match(input.timestamp),
+            Filter::Field(filter) => filter.is_match(input.fields),
+            Filter::MinLevel(min) => input.level.is_none_or(|level| level >= *min),
+            Filter::Level(level) => input.level == Some(*level),
+            Filter::All(filters) => filters.iter().all(|f| f.is_match(input)),
+            Filter::Any(filters) => filters.iter().any(|f| f.is_match(input)),
+            Filter::Not(filter) => !filter.is_match(input),
+        }
+    }
+
+    /// Tells if [`Filter::is_match`] needs the line timestamp
+    pub fn needs_timestamp(&self) -> bool {
+        match self {
+            Filter::Regex(_) | Filter::Field(_) | Filter::MinLevel(_) | Filter::Level(_) => false,
+            Filter::Time(_) => true,
+            Filter::All(filters) | Filter::Any(filters) => {
+                filters.iter().any(Filter::needs_timestamp)
+            }
+            Filter::Not(filter) => filter.needs_timestamp(),
+        }
+    }
+
+    /// Tells if [`Filter::is_match`] needs the line fields
+    pub fn needs_fields(&self) -> bool {
+        match self {
+            Filter::Regex(_) | Filter::Time(_) | Filter::MinLevel(_) | Filter::Level(_) => false,
+            Filter::Field(_) => true,
+            Filter::All(filters) | Filter::Any(filters) => filters.iter().any(Filter::needs_fields),
+            Filter::Not(filter) => filter.needs_fields(),
+        }
+    }
+
+    /// Tells if [`Filter::is_match`] needs the line level
+    pub fn needs_level(&self) -> bool {
+        match self {
+            Filter::Regex(_) | Filter::Time(_) | Filter::Field(_) => false,
+            Filter::MinLevel(_) | Filter::Level(_) => true,
+            Filter::All(filters) | Filter::Any(filters) => filters.iter().any(Filter::needs_level),
+            Filter::Not(filter) => filter.needs_level(),
+        }
+    }
+}
+
+impl From<RegexFilter> for Filter {
+    fn from(filter: RegexFilter) -> Self {
+        Filter::Regex(filter)
+    }
+}
+
+impl From<TimeFilter> for Filter {
+    fn from(filter: TimeFilter) -> Self {
+        Filter::Time(filter)
+    }
+}
+
+impl From<FieldFilter> for Filter {
+    fn from(filter: FieldFilter) -> Self {
+        Filter::Field(filter)
+    }
+}
+
+impl From<Regex> for Filter {
+    fn from(pattern: Regex) -> Self {
+        Filter::Regex(pattern.into())
+    }
+}
+
+impl TryFrom<&str> for Filter {
+    type Error = regex::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        RegexFilter::try_from(value).map(Filter::Regex)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct RegexFilter {
     pub pattern: Regex,
@@ -28,3 +149,213 @@ impl TryFrom<&str> for RegexFilter {
         Regex::new(value).map(RegexFilter::from)
     }
 }
+
+/// Number of lines shown around every line passing filters, like `grep -B` and `-A` do
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ContextLines {
+    pub before: usize,
+    pub after: usize,
+}
+
+/// Passes lines with timestamp within inclusive range. Missing bound leaves the range open on that side. Lines
+/// without timestamp are outside of any range.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimeFilter {
+    pub from: Option<Timestamp>,
+    pub to: Option<Timestamp>,
+    pub negate: bool,
+}
+
+impl TimeFilter {
+    pub fn is_match(&self, timestamp: Option<Timestamp>) -> bool {
+        let within = timestamp.is_some_and(|t| {
+            self.from.is_none_or(|from| from <= t) && self.to.is_none_or(|to| t <= to)
+        });
+        within ^ self.negate
+    }
+}
+
+/// Condition on a single field of a structured log line. Lines without the field never match (unless negated).
+#[derive(Clone, Debug)]
+pub struct FieldFilter {
+    pub field: String,
+    pub predicate: FieldPredicate,
+    pub negate: bool,
+}
+
+#[derive(Clone, Debug)]
+pub enum FieldPredicate {
+    Exists,
+    Equals(String),
+    NotEquals(String),
+    Matches(Regex),
+    /// Compares field value as a number. Value may have a unit suffix (`12ms`) which is ignored.
+    Compare(Comparison, f64),
+    /// Compares field value as a [`Level`] name (`level >= warn`)
+    CompareLevel(Comparison, Level),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn holds<T: PartialOrd>(self, value: T, expected: T) -> bool {
+        match self {
+            Comparison::Less => value < expected,
+            Comparison::LessOrEqual => value <= expected,
+            Comparison::Greater => value > expected,
+            Comparison::GreaterOrEqual => value >= expected,
+        }
+    }
+}
+
+impl FieldFilter {
+    pub fn is_match(&self, fields: Option<&Fields>) -> bool {
+        let value = fields.and_then(|fields| fields.get(&self.field));
+        let matches = value.is_some_and(|value| match &self.predicate {
+            FieldPredicate::Exists => true,
+            FieldPredicate::Equals(expected) => value == expected,
+            FieldPredicate::NotEquals(expected) => value != expected,
+            FieldPredicate::Matches(pattern) => pattern.is_match(value),
+            FieldPredicate::Compare(comparison, expected) => {
+                parse_number(value).is_some_and(|value| comparison.holds(value, *expected))
+            }
+            FieldPredicate::CompareLevel(comparison, expected) => value
+                .parse::<Level>()
+                .is_ok_and(|value| comparison.holds(value, *expected)),
+        });
+        matches ^ self.negate
+    }
+}
+
+/// Operators of field filters, longest first so `>=` is not taken for `>`
+const FIELD_OPERATORS: [&str; 9] = ["exists", "!=", "==", ">=", "<=", "=", "~", ">", "<"];
+
+/// Parses a number optionally followed by an alphabetic unit (`12`, `-0.5`, `12ms`)
+fn parse_number(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let unit_len: usize = value
+        .chars()
+        .rev()
+        .take_while(|c| c.is_alphabetic())
+        .map(char::len_utf8)
+        .sum();
+    value[..value.len() - unit_len].parse().ok()
+}
+
+impl FromStr for FieldFilter {
+    type Err = String;
+
+    /// Parses `field op value` where op is one of `=`, `!=`, `~` (regex), `>`, `>=`, `<`, `<=`, or `field exists`.
+    /// Spaces around the operator are optional. Field names are dotted paths of word characters and `-`.
+    /// Comparisons take a number or a level name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid field filter {s:?}, expected e.g. \"status >= 500\"");
+        let s = s.trim();
+        let field_len = s
+            .find(|c: char| !(c.is_alphanumeric() || "_.-".contains(c)))
+            .unwrap_or(s.len());
+        let (field, rest) = s.split_at(field_len);
+        if field.is_empty() {
+            return Err(invalid());
+        }
+        let rest = rest.trim_start();
+        let operator = *FIELD_OPERATORS
+            .iter()
+            .find(|op| rest.starts_with(*op))
+            .ok_or_else(invalid)?;
+        let value = rest[operator.len()..].trim();
+
+        let compare = |comparison| match (value.parse(), value.parse()) {
+            (Ok(number), _) => Ok(FieldPredicate::Compare(comparison, number)),
+            (_, Ok(level)) => Ok(FieldPredicate::CompareLevel(comparison, level)),
+            _ => Err(invalid()),
+        };
+        let predicate = match operator {
+            "exists" if value.is_empty() => FieldPredicate::Exists,
+            "=" | "==" => FieldPredicate::Equals(String::from(value)),
+            "!=" => FieldPredicate::NotEquals(String::from(value)),
+            "~" => FieldPredicate::Matches(Regex::new(value).map_err(|e| e.to_string())?),
+            "<" => compare(Comparison::Less)?,
+            "<=" => compare(Comparison::LessOrEqual)?,
+            ">" => compare(Comparison::Greater)?,
+            ">=" => compare(Comparison::GreaterOrEqual)?,
+            _ => return Err(invalid()),
+        };
+        Ok(FieldFilter {
+            field: String::from(field),
+            predicate,
+            negate: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn regex(pattern: &str) -> Filter {
+        pattern.try_into().unwrap()
+    }
+
+    #[test]
+    fn composes_filters() {
+        // ERROR or WARN, but not from healthcheck
+        let filter = Filter::All(vec![
+            Filter::Any(vec![regex("ERROR"), regex("WARN")]),
+            Filter::Not(Box::new(regex("healthcheck"))),
+        ]);
+
+        let is_match = |text| filter.is_match(&FilterInput::text(text));
+        assert!(is_match("ERROR db down"));
+        assert!(is_match("WARN slow query"));
+        assert!(!is_match("INFO started"));
+        assert!(!is_match("WARN healthcheck slow"));
+        assert!(!filter.needs_timestamp());
+        assert!(Filter::Not(Box::new(TimeFilter::default().into())).needs_timestamp());
+    }
+
+    #[test]
+    fn matches_fields() {
+        let fields: Fields = [
+            ("status", "503"),
+            ("dur", "12ms"),
+            ("user.name", "bob"),
+            ("level", "WARNING"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (String::from(k), String::from(v)))
+        .collect();
+        let is_match = |filter: &str| {
+            let filter: Filter = filter.parse::<FieldFilter>().unwrap().into();
+            filter.is_match(&FilterInput {
+                fields: Some(&fields),
+                ..Default::default()
+            })
+        };
+
+        assert!(is_match("status >= 500"));
+        assert!(is_match("status>=500"));
+        assert!(is_match("status  >=  500"));
+        assert!(is_match("user.name=bob"));
+        assert!(!is_match("status < 500"));
+        assert!(is_match("dur > 10"));
+        assert!(is_match("user.name = bob"));
+        assert!(is_match("user.name != alice"));
+        assert!(is_match("user.name ~ ^b"));
+        assert!(is_match("status exists"));
+        assert!(is_match("level >= warn"));
+        assert!(!is_match("level > warn"));
+        assert!(!is_match("missing exists"));
+        assert!(!is_match("missing != x"));
+        assert!("status >= many".parse::<FieldFilter>().is_err());
+        assert!("just some text".parse::<FieldFilter>().is_err());
+        assert!("statusexists".parse::<FieldFilter>().is_err());
+        assert!(">= 500".parse::<FieldFilter>().is_err());
+    }
+}
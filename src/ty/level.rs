@@ -0,0 +1,119 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use super::text::line_prefix;
+
+/// Length of the line start searched for a level, see [`line_prefix`]
+const SEARCH_PREFIX_CHARS: usize = 100;
+
+/// Severity of a log line, common to all log formats. Ordered from the least to the most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl Level {
+    pub const ALL: [Level; 6] = [
+        Level::Trace,
+        Level::Debug,
+        Level::Info,
+        Level::Warn,
+        Level::Error,
+        Level::Fatal,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Trace => "trace",
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+            Level::Fatal => "fatal",
+        }
+    }
+
+    /// Finds level name near the line start: an uppercase word (`WARN`, `ERROR:`) or a name in brackets in any case
+    /// (`[warn]`, `<Error>`)
+    pub fn detect(line: &str) -> Option<Level> {
+        static PATTERN: OnceLock<Regex> = OnceLock::new();
+        let pattern = PATTERN.get_or_init(|| {
+            let names =
+                "TRACE|DEBUG|INFO|NOTICE|WARN|WARNING|ERROR|ERR|SEVERE|FATAL|CRIT|CRITICAL|PANIC";
+            Regex::new(&format!(r"\b({names})\b|[\[<(](?i:({names}))[\]>)]")).unwrap()
+        });
+        let captures = pattern.captures(line_prefix(line, SEARCH_PREFIX_CHARS))?;
+        captures.get(1).or(captures.get(2))?.as_str().parse().ok()
+    }
+
+    /// Maps syslog severity (0 emergency .. 7 debug). Notice counts as info, emergency, alert and critical as fatal.
+    pub fn from_syslog_severity(severity: u8) -> Option<Level> {
+        match severity {
+            0..=2 => Some(Level::Fatal),
+            3 => Some(Level::Error),
+            4 => Some(Level::Warn),
+            5 | 6 => Some(Level::Info),
+            7 => Some(Level::Debug),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    /// Accepts level names used by common logging libraries and syslog, case insensitive
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let level = match s.to_ascii_lowercase().as_str() {
+            "trace" | "trc" | "finest" | "verbose" => Level::Trace,
+            "debug" | "dbg" | "fine" => Level::Debug,
+            "info" | "inf" | "information" | "informational" | "notice" => Level::Info,
+            "warn" | "wrn" | "warning" => Level::Warn,
+            "error" | "err" | "eror" | "severe" => Level::Error,
+            "fatal" | "crit" | "critical" | "alert" | "emerg" | "emergency" | "panic" => {
+                Level::Fatal
+            }
+            _ => return Err(format!("unknown level: {s}")),
+        };
+        Ok(level)
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detects_level_near_line_start() {
+        assert_eq!(
+            Level::detect("2023-03-14 08:15:02 WARN disk almost full"),
+            Some(Level::Warn)
+        );
+        assert_eq!(Level::detect("ERROR: no config"), Some(Level::Error));
+        assert_eq!(
+            Level::detect("08:15:02 [debug] cache miss"),
+            Some(Level::Debug)
+        );
+        assert_eq!(Level::detect("<Critical> power lost"), Some(Level::Fatal));
+        assert_eq!(Level::detect("retrying after error"), None);
+        assert_eq!(Level::detect("INFORMATION"), None);
+        assert_eq!(
+            Level::detect(&format!("{} ERROR late", "x".repeat(100))),
+            None
+        );
+    }
+}
@@ -1,3 +1,6 @@
 pub mod filter;
+pub mod level;
+pub mod pattern;
+pub mod record;
 pub mod span;
 pub mod text;
@@ -0,0 +1,155 @@
+use regex::{Regex, RegexBuilder};
+
+/// How pattern text is interpreted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PatternKind {
+    /// Text is matched as is, no character has special meaning
+    #[default]
+    Literal,
+    Regex,
+}
+
+impl PatternKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PatternKind::Literal => "literal",
+            PatternKind::Regex => "regex",
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            PatternKind::Literal => PatternKind::Regex,
+            PatternKind::Regex => PatternKind::Literal,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaseMode {
+    Sensitive,
+    Insensitive,
+    /// Case insensitive unless pattern contains an uppercase letter
+    #[default]
+    Smart,
+}
+
+impl CaseMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CaseMode::Sensitive => "case-sensitive",
+            CaseMode::Insensitive => "case-insensitive",
+            CaseMode::Smart => "smart-case",
+        }
+    }
+
+    /// Next mode in order sensitive, insensitive, smart
+    pub fn cycled(self) -> Self {
+        match self {
+            CaseMode::Sensitive => CaseMode::Insensitive,
+            CaseMode::Insensitive => CaseMode::Smart,
+            CaseMode::Smart => CaseMode::Sensitive,
+        }
+    }
+}
+
+/// Search, highlight or filter pattern as typed by the user, compiled to [`Regex`]
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    text: String,
+    kind: PatternKind,
+    case: CaseMode,
+    regex: Regex,
+}
+
+impl Pattern {
+    pub fn new(text: &str, kind: PatternKind, case: CaseMode) -> Result<Self, regex::Error> {
+        let source = match kind {
+            PatternKind::Literal => regex::escape(text),
+            PatternKind::Regex => String::from(text),
+        };
+        let ignore_case = match case {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => !has_uppercase(text, kind),
+        };
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(ignore_case)
+            .build()?;
+        Ok(Pattern {
+            text: String::from(text),
+            kind,
+            case,
+            regex,
+        })
+    }
+
+    /// Text the pattern was created from
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn kind(&self) -> PatternKind {
+        self.kind
+    }
+
+    pub fn case(&self) -> CaseMode {
+        self.case
+    }
+
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        self.regex.is_match(line)
+    }
+}
+
+impl From<Pattern> for Regex {
+    fn from(pattern: Pattern) -> Self {
+        pattern.regex
+    }
+}
+
+/// Looks for uppercase letters. Regex escapes (e.g. `\S`, `\W`) are not letters to match, so they are skipped.
+fn has_uppercase(text: &str, kind: PatternKind) -> bool {
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' && kind == PatternKind::Regex {
+            chars.next();
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matches_literal_text() {
+        let pattern = Pattern::new("foo.bar(", PatternKind::Literal, CaseMode::Sensitive).unwrap();
+        assert!(pattern.is_match("call foo.bar(1)"));
+        assert!(!pattern.is_match("call fooxbar(1)"));
+        assert!(Pattern::new("foo.bar(", PatternKind::Regex, CaseMode::Sensitive).is_err());
+    }
+
+    #[test]
+    fn applies_case_mode() {
+        let matches = |text: &str, kind, case, line: &str| {
+            Pattern::new(text, kind, case).unwrap().is_match(line)
+        };
+        use CaseMode::*;
+        use PatternKind::*;
+
+        assert!(!matches("error", Literal, Sensitive, "ERROR"));
+        assert!(matches("error", Literal, Insensitive, "ERROR"));
+        assert!(matches("error", Literal, Smart, "ERROR"));
+        assert!(!matches("Error", Literal, Smart, "ERROR"));
+        assert!(matches(r"\Serror", Regex, Smart, "XERROR"));
+        assert!(!matches(r"\Serror", Literal, Smart, r"\serror"));
+    }
+}
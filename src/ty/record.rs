@@ -0,0 +1,32 @@
+use std::str::FromStr;
+
+use regex::Regex;
+
+/// Tells where log records start. Lines between record starts (e.g. stack traces) continue the record of the
+/// preceding line, so filters see a record as a whole.
+#[derive(Clone, Debug, Default)]
+pub enum RecordStart {
+    /// In logs with timestamps lines without one continue the record, in other logs lines starting with whitespace
+    /// do
+    #[default]
+    Auto,
+    /// Records start at lines matching the pattern
+    Pattern(Regex),
+    /// Every line is a record
+    Line,
+}
+
+impl FromStr for RecordStart {
+    type Err = String;
+
+    /// Accepts `auto`, `line` or a pattern matching the first line of a record
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(RecordStart::Auto),
+            "line" => Ok(RecordStart::Line),
+            _ => Regex::new(s)
+                .map(RecordStart::Pattern)
+                .map_err(|e| e.to_string()),
+        }
+    }
+}
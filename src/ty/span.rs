@@ -5,9 +5,16 @@ pub struct Span {
 }
 
 impl Span {
+    /// Returns span without first `n` characters
     pub fn remove_left(&self, n: usize) -> SpanRef<'_> {
+        let start = self
+            .content
+            .char_indices()
+            .nth(n)
+            .map(|(pos, _)| pos)
+            .unwrap_or(self.content.len());
         SpanRef {
-            content: &self.content[n..],
+            content: &self.content[start..],
             kind: self.kind,
         }
     }
@@ -1,11 +1,22 @@
 use std::fmt::Display;
 
 use super::span::{Span, SpanRef};
+use crate::{Level, Timestamp};
 
 #[derive(Debug, Clone)]
 pub struct TextLine {
     pub line_num: usize,
     pub spans: Vec<Span>,
+    /// Line contained undecodable bytes that were replaced
+    pub lossy: bool,
+    /// Tag of the stream line came from, see [`crate::Sherlog::append_from`]
+    pub origin: u16,
+    /// Normalized line timestamp, see [`crate::TimestampFormat`]
+    pub timestamp: Option<Timestamp>,
+    /// Line severity, see [`crate::Sherlog::level`]
+    pub level: Option<Level>,
+    /// Line is shown only as context of lines passing filters, see [`crate::ContextLines`]
+    pub context: bool,
 }
 
 impl From<(usize, String)> for TextLine {
@@ -13,6 +24,11 @@ impl From<(usize, String)> for TextLine {
         TextLine {
             line_num: value.0,
             spans: vec![value.1.into()],
+            lossy: false,
+            origin: 0,
+            timestamp: None,
+            level: None,
+            context: false,
         }
     }
 }
@@ -21,6 +37,16 @@ impl From<(usize, String)> for TextLine {
 pub struct TextLineRef<'a> {
     pub line_num: usize,
     pub spans: Vec<SpanRef<'a>>,
+    /// Line contained undecodable bytes that were replaced
+    pub lossy: bool,
+    /// Tag of the stream line came from, see [`crate::Sherlog::append_from`]
+    pub origin: u16,
+    /// Normalized line timestamp, see [`crate::TimestampFormat`]
+    pub timestamp: Option<Timestamp>,
+    /// Line severity, see [`crate::Sherlog::level`]
+    pub level: Option<Level>,
+    /// Line is shown only as context of lines passing filters, see [`crate::ContextLines`]
+    pub context: bool,
 }
 
 impl<'a> TextLineRef<'a> {
@@ -28,6 +54,11 @@ impl<'a> TextLineRef<'a> {
         TextLineRef {
             line_num,
             spans: vec![SpanRef::raw(text)],
+            lossy: false,
+            origin: 0,
+            timestamp: None,
+            level: None,
+            context: false,
         }
     }
 
@@ -35,6 +66,11 @@ impl<'a> TextLineRef<'a> {
         TextLine {
             line_num: self.line_num,
             spans: self.spans.iter().map(SpanRef::to_span).collect(),
+            lossy: self.lossy,
+            origin: self.origin,
+            timestamp: self.timestamp,
+            level: self.level,
+            context: self.context,
         }
     }
 }
@@ -47,3 +83,12 @@ impl Display for TextLineRef<'_> {
         Ok(())
     }
 }
+
+/// Beginning of `line` up to `chars` characters long. Timestamps and levels are looked for only there, so message
+/// content (`retrying after ERROR`) is not mistaken for them.
+pub(crate) fn line_prefix(line: &str, chars: usize) -> &str {
+    match line.char_indices().nth(chars) {
+        Some((end, _)) => &line[..end],
+        None => line,
+    }
+}